@@ -0,0 +1,353 @@
+use crate::{
+    compute::{initial::InitialKind, simulation::ComputeBackend},
+    config::Config,
+    pattern::{image::ImageMapping, Region},
+    render::{brush::BrushShape, colormap::Colormap},
+    shared::{boundary::Boundary, sim_params::SimulationMode},
+};
+use anyhow::{anyhow, bail, Result};
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+use winit::dpi::PhysicalSize;
+
+/// Generations run without a window when `--steps` is not given
+const DEFAULT_STEPS: u64 = 1000;
+
+/// Cellular automata and other simulations on the GPU.
+/// Without a subcommand, runs the simulation in a window
+#[derive(Parser)]
+#[command(name = "cells", version, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[command(flatten)]
+    pub run: RunArgs,
+    /// Runs without a window like the headless subcommand,
+    /// kept for `cells --headless --steps N --output DIR` from before subcommands
+    #[arg(long, hide = true)]
+    pub headless: bool,
+    #[arg(long, hide = true, requires = "headless")]
+    pub steps: Option<u64>,
+    #[arg(long, hide = true, requires = "headless")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Runs the simulation in a window
+    Run(RunArgs),
+    /// Runs the simulation without a window and writes the final state and statistics
+    Headless {
+        #[command(flatten)]
+        simulation: SimulationArgs,
+        /// Generations to run
+        #[arg(long, default_value_t = DEFAULT_STEPS)]
+        steps: u64,
+        /// Directory to write `state.f32` and `stats.toml` into
+        #[arg(long, short, default_value = ".")]
+        output: PathBuf,
+        /// Pattern file to also write the numbered states of the final generation into,
+        /// like out.rle, or image of its raw values, like out.png. Images shade the states
+        /// of discrete modes from black to white, and clamp continuous values within 0 and 1
+        #[arg(long)]
+        export: Option<PathBuf>,
+        /// Part of the grid to export, as X,Y,WIDTHxHEIGHT
+        #[arg(long)]
+        region: Option<Region>,
+        /// Bits per sample of exported images, 8 or 16
+        #[arg(long, default_value_t = 8, value_parser = parse_bit_depth)]
+        bit_depth: u8,
+        /// Snapshot file to also save the whole final state into, to resume with --resume
+        #[arg(long)]
+        snapshot: Option<PathBuf>,
+    },
+    /// Converts a pattern file to another format, chosen by the output's extension
+    Convert {
+        input: PathBuf,
+        output: PathBuf,
+        /// Size of the grid in the input, for formats that don't store it.
+        /// Macrocells larger than it are refused
+        #[arg(long, value_parser = parse_grid_size)]
+        size: Option<PhysicalSize<u32>>,
+        /// Values per cell in the input, for formats that don't store it
+        #[arg(long, default_value_t = 1)]
+        channels: usize,
+        /// Part of the input to convert, as X,Y,WIDTHxHEIGHT
+        #[arg(long)]
+        region: Option<Region>,
+    },
+    /// Measures how many generations per second the simulation runs without a window
+    Bench {
+        #[command(flatten)]
+        simulation: SimulationArgs,
+        /// Generations to time
+        #[arg(long, default_value_t = 1000)]
+        steps: u64,
+        /// Generations to run before timing, to leave out startup costs
+        #[arg(long, default_value_t = 100)]
+        warmup: u64,
+    },
+}
+
+impl Cli {
+    /// The subcommand to run, headless with `--headless` and in a window without one
+    pub fn command(self) -> Command {
+        match (self.command, self.headless) {
+            (Some(command), _) => command,
+            (None, true) => Command::Headless {
+                simulation: self.run.simulation,
+                steps: self.steps.unwrap_or(DEFAULT_STEPS),
+                output: self.output.unwrap_or_else(|| PathBuf::from(".")),
+                export: None,
+                region: None,
+                bit_depth: 8,
+                snapshot: None,
+            },
+            (None, false) => Command::Run(self.run),
+        }
+    }
+}
+
+#[derive(Args)]
+pub struct RunArgs {
+    #[command(flatten)]
+    pub simulation: SimulationArgs,
+    #[command(flatten)]
+    pub window: WindowArgs,
+}
+
+#[derive(Args)]
+pub struct SimulationArgs {
+    /// TOML file of settings, overridden by the flags given alongside it
+    #[arg(long, short)]
+    pub config: Option<PathBuf>,
+    /// Simulation mode: life (default), physarum, gray-scott, lenia, smooth-life or larger-than-life
+    #[arg(long, short)]
+    pub mode: Option<SimulationMode>,
+    /// Cells in the grid, as WIDTHxHEIGHT [default: 512x512]
+    #[arg(long, short, value_parser = parse_grid_size)]
+    pub size: Option<PhysicalSize<u32>>,
+    /// Rule of Life like B3/S23 or /C3 for Generations, or of Larger than Life like R5,C0,M1,S34..58,B34..45,NM
+    #[arg(long, short)]
+    pub rule: Option<String>,
+    /// Lenia creature to start from [default: orbium]
+    #[arg(long)]
+    pub creature: Option<String>,
+    /// Use discrete time SmoothLife instead of the time-continuous variant
+    #[arg(long, overrides_with = "no_discrete")]
+    pub discrete: bool,
+    /// Use time-continuous SmoothLife, even if the config sets `discrete = true`
+    #[arg(long, overrides_with = "discrete")]
+    pub no_discrete: bool,
+    /// Seed of random initial states [default: 0]
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Initial state: default (the mode's own), empty, random, box, noise, soup-c2, soup-c4 or soup-d8
+    #[arg(long)]
+    pub initial: Option<InitialKind>,
+    /// Fraction of cells alive in random states, or noise threshold in discrete modes [default: 0.5]
+    #[arg(long)]
+    pub density: Option<f32>,
+    /// Fraction of the grid covered by boxes and soups [default: 0.25]
+    #[arg(long)]
+    pub extent: Option<f32>,
+    /// Width in cells of the coarsest noise features [default: 32]
+    #[arg(long)]
+    pub noise_scale: Option<f32>,
+    /// File of cells to start from: a whole grid replacing the initial state,
+    /// or a .rle, .cells, .lif, .mc or .png pattern stamped over it
+    #[arg(long, short)]
+    pub pattern: Option<PathBuf>,
+    /// Cell of the grid to place the top left of a pattern at, as X,Y [default: centred]
+    #[arg(long, value_parser = parse_position, allow_hyphen_values = true)]
+    pub pattern_at: Option<[i32; 2]>,
+    /// How the pixels of a PNG pattern become cells: threshold (alive from half brightness),
+    /// threshold=T, or luminance in continuous modes [default: threshold]
+    #[arg(long)]
+    pub image_mapping: Option<ImageMapping>,
+    /// Snapshot to continue from, whose grid, parameters and state replace
+    /// the other simulation settings
+    #[arg(long)]
+    pub resume: Option<PathBuf>,
+    /// Boundary: torus (default), dead, alive, mirror, klein-bottle or cross-surface
+    #[arg(long, short)]
+    pub boundary: Option<Boundary>,
+    /// Where to step the simulation: auto (default), gpu or cpu.
+    /// The grid is kept in textures in every case, so cpu still needs an adapter,
+    /// though one without compute shaders or a software one will do
+    #[arg(long)]
+    pub backend: Option<ComputeBackend>,
+    /// Graphics APIs to look for adapters in, comma separated: all (default), vulkan, metal, dx12, dx11 or gl
+    #[arg(long, value_parser = parse_graphics_backends)]
+    pub graphics: Option<wgpu::Backends>,
+}
+
+#[derive(Args)]
+pub struct WindowArgs {
+    /// Title of the window [default: Cells]
+    #[arg(long)]
+    pub title: Option<String>,
+    /// Present mode: fifo (default), fifo-relaxed, mailbox, immediate, auto-vsync or auto-no-vsync
+    #[arg(long, value_parser = parse_present_mode)]
+    pub present_mode: Option<wgpu::PresentMode>,
+    /// Simulation ticks per second [default: 60]
+    #[arg(long)]
+    pub tick_rate: Option<u32>,
+    /// Generations stepped at once with shift and period [default: 10]
+    #[arg(long)]
+    pub step_batch: Option<u32>,
+    /// Radius of the brush painting cells, in cells [default: 2]
+    #[arg(long)]
+    pub brush_radius: Option<u32>,
+    /// Shape of the brush: circle (default) or square
+    #[arg(long)]
+    pub brush_shape: Option<BrushShape>,
+    /// Colors of continuous states: viridis (default), inferno, turbo or grayscale
+    #[arg(long)]
+    pub colormap: Option<Colormap>,
+}
+
+impl RunArgs {
+    pub fn config(&self) -> Result<Config> {
+        let mut config = self.simulation.config()?;
+        self.window.apply(&mut config);
+        Ok(config)
+    }
+}
+
+impl SimulationArgs {
+    /// The configuration file if any, with the flags applied over it
+    pub fn config(&self) -> Result<Config> {
+        let mut config = match &self.config {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+        self.apply(&mut config);
+        Ok(config)
+    }
+
+    /// `--discrete` or `--no-discrete`, whichever was given last
+    fn discrete(&self) -> Option<bool> {
+        match (self.discrete, self.no_discrete) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        }
+    }
+
+    fn apply(&self, config: &mut Config) {
+        if let Some(size) = self.size {
+            config.grid.width = size.width;
+            config.grid.height = size.height;
+        }
+        override_with(&mut config.grid.boundary, self.boundary);
+        override_with(&mut config.simulation.mode, self.mode);
+        if self.rule.is_some() {
+            config.simulation.rule = self.rule.clone();
+        }
+        override_with(&mut config.simulation.discrete, self.discrete());
+        override_with(&mut config.simulation.backend, self.backend);
+        override_with(&mut config.simulation.graphics, self.graphics);
+        override_with(&mut config.initial.seed, self.seed);
+        override_with(&mut config.initial.kind, self.initial);
+        override_with(&mut config.initial.density, self.density);
+        override_with(&mut config.initial.extent, self.extent);
+        override_with(&mut config.initial.noise_scale, self.noise_scale);
+        override_with(&mut config.initial.creature, self.creature.clone());
+        if self.pattern.is_some() {
+            config.initial.pattern = self.pattern.clone();
+        }
+        if self.pattern_at.is_some() {
+            config.initial.pattern_position = self.pattern_at;
+        }
+        override_with(&mut config.initial.image_mapping, self.image_mapping);
+        if self.resume.is_some() {
+            config.initial.resume = self.resume.clone();
+        }
+    }
+}
+
+impl WindowArgs {
+    fn apply(&self, config: &mut Config) {
+        let window = &mut config.window;
+        override_with(&mut window.title, self.title.clone());
+        override_with(&mut window.present_mode, self.present_mode);
+        override_with(&mut window.tick_rate, self.tick_rate);
+        override_with(&mut window.step_batch, self.step_batch);
+        override_with(&mut window.brush_radius, self.brush_radius);
+        override_with(&mut window.brush_shape, self.brush_shape);
+        override_with(&mut window.colormap, self.colormap);
+    }
+}
+
+/// Replaces a setting with the flag, if it was given
+fn override_with<T>(setting: &mut T, flag: Option<T>) {
+    if let Some(flag) = flag {
+        *setting = flag;
+    }
+}
+
+/// Parses a grid size like `4096x4096`
+fn parse_grid_size(size: &str) -> Result<PhysicalSize<u32>> {
+    let parse = || -> Result<_> {
+        let (width, height) = size
+            .split_once('x')
+            .ok_or_else(|| anyhow!("Expected WIDTHxHEIGHT"))?;
+        let (width, height) = (width.parse::<u32>()?, height.parse::<u32>()?);
+        if width == 0 || height == 0 {
+            bail!("Grid must be at least one cell wide and high");
+        }
+        Ok(PhysicalSize::new(width, height))
+    };
+    // Clap only shows the outermost error, so the cause is folded into it
+    parse().map_err(|error| anyhow!("Invalid grid size '{size}': {error:#}"))
+}
+
+/// Parses a cell position like `-4,10`
+fn parse_position(position: &str) -> Result<[i32; 2]> {
+    let (x, y) = position
+        .split_once(',')
+        .ok_or_else(|| anyhow!("Invalid position '{position}': expected X,Y"))?;
+    let parse = |coordinate: &str| {
+        coordinate
+            .trim()
+            .parse::<i32>()
+            .map_err(|error| anyhow!("Invalid position '{position}': {error}"))
+    };
+    Ok([parse(x)?, parse(y)?])
+}
+
+/// Parses the bits per sample of an image, 8 or 16
+fn parse_bit_depth(depth: &str) -> Result<u8> {
+    match depth {
+        "8" => Ok(8),
+        "16" => Ok(16),
+        _ => bail!("Invalid bit depth '{depth}', expected 8 or 16"),
+    }
+}
+
+pub fn parse_present_mode(mode: &str) -> Result<wgpu::PresentMode> {
+    Ok(match mode.to_ascii_lowercase().as_str() {
+        "fifo" => wgpu::PresentMode::Fifo,
+        "fifo-relaxed" => wgpu::PresentMode::FifoRelaxed,
+        "mailbox" => wgpu::PresentMode::Mailbox,
+        "immediate" => wgpu::PresentMode::Immediate,
+        "auto-vsync" => wgpu::PresentMode::AutoVsync,
+        "auto-no-vsync" => wgpu::PresentMode::AutoNoVsync,
+        _ => bail!(
+            "Unknown present mode '{mode}', expected fifo, fifo-relaxed, mailbox, immediate, \
+            auto-vsync or auto-no-vsync"
+        ),
+    })
+}
+
+pub fn parse_graphics_backends(backends: &str) -> Result<wgpu::Backends> {
+    if backends.eq_ignore_ascii_case("all") {
+        return Ok(wgpu::Backends::all());
+    }
+    let parsed = wgpu::util::parse_backends_from_comma_list(&backends.to_ascii_lowercase());
+    if parsed.is_empty() {
+        bail!("Unknown graphics APIs '{backends}', expected all, vulkan, metal, dx12, dx11 or gl");
+    }
+    Ok(parsed)
+}
@@ -1 +1,11 @@
+pub mod compute_pass;
+pub mod cpu_simulation;
+pub mod gpu_simulation;
+pub mod gray_scott;
+pub mod initial;
+pub mod larger_than_life;
+pub mod lenia;
+pub mod life;
+pub mod physarum;
 pub mod simulation;
+pub mod smooth_life;
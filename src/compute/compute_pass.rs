@@ -0,0 +1,120 @@
+use crate::shared::{
+    shader::Shader,
+    sim_params::{SimulationParams, SimulationParamsBuf},
+    texture::Texture,
+};
+
+/// One compute dispatch of a simulation step over the ping-pong textures.
+///
+/// Every pass binds the current texture at binding 0, the next texture as write-only storage at
+/// binding 1 and the simulation parameters at binding 2; mode-specific resources follow from 3.
+pub struct ComputePass {
+    pipeline: wgpu::ComputePipeline,
+    // bind_groups[i] reads textures[i] and writes the other one
+    bind_groups: [wgpu::BindGroup; 2],
+    workgroups: (u32, u32, u32),
+}
+
+impl ComputePass {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        device: &wgpu::Device,
+        label: &str,
+        shader: &Shader,
+        entry_point: &str,
+        textures: &[Texture; 2],
+        sim_params: &SimulationParamsBuf,
+        extra_bindings: &[(wgpu::BindingType, wgpu::BindingResource)],
+        workgroups: (u32, u32, u32),
+    ) -> Self {
+        let mut entries = vec![
+            wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: textures[0].texture_format,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: wgpu::BufferSize::new(
+                    std::mem::size_of::<SimulationParams>() as _
+                ),
+            },
+        ];
+        entries.extend(extra_bindings.iter().map(|(ty, _)| *ty));
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(&format!("{label} Bind Group Layout")),
+            entries: &entries
+                .into_iter()
+                .enumerate()
+                .map(|(binding, ty)| wgpu::BindGroupLayoutEntry {
+                    binding: binding as u32,
+                    ty,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    count: None,
+                })
+                .collect::<Vec<_>>(),
+        });
+        let bind_groups = [0, 1].map(|src| {
+            let mut resources = vec![
+                wgpu::BindingResource::TextureView(&textures[src].texture_view),
+                wgpu::BindingResource::TextureView(&textures[1 - src].texture_view),
+                sim_params.params_buf.as_entire_binding(),
+            ];
+            resources.extend(extra_bindings.iter().map(|(_, resource)| resource.clone()));
+
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(&format!("{label} Bind Group {src}")),
+                layout: &bind_group_layout,
+                entries: &resources
+                    .into_iter()
+                    .enumerate()
+                    .map(|(binding, resource)| wgpu::BindGroupEntry {
+                        binding: binding as u32,
+                        resource,
+                    })
+                    .collect::<Vec<_>>(),
+            })
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&format!("{label} Pipeline Layout")),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(&format!("{label} Pipeline")),
+            layout: Some(&pipeline_layout),
+            module: &shader.module,
+            entry_point,
+        });
+
+        Self {
+            pipeline,
+            bind_groups,
+            workgroups,
+        }
+    }
+
+    /// Workgroup counts covering every texel of the textures with 8x8 workgroups
+    pub fn texel_workgroups(textures: &[Texture; 2]) -> (u32, u32, u32) {
+        (
+            textures[0].texture.width().div_ceil(8),
+            textures[0].texture.height().div_ceil(8),
+            1,
+        )
+    }
+
+    pub fn dispatch<'a>(&'a self, compute_pass: &mut wgpu::ComputePass<'a>, current: usize) {
+        compute_pass.set_pipeline(&self.pipeline);
+        compute_pass.set_bind_group(0, &self.bind_groups[current], &[]);
+        let (x, y, z) = self.workgroups;
+        compute_pass.dispatch_workgroups(x, y, z);
+    }
+}
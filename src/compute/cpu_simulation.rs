@@ -0,0 +1,193 @@
+use crate::shared::{
+    boundary::Boundary,
+    larger_than_life::LargerThanLifeRule,
+    rule::{Rule, NEIGHBORS},
+    sim_params::{SimulationMode, SimulationParams},
+};
+
+/// Reference implementation of the cell update rules in `life.wgsl` and
+/// `larger_than_life.wgsl`, stepping a row-major grid of cells on the CPU
+pub struct CpuSimulation {
+    pub generation: usize,
+    width: usize,
+    height: usize,
+    mode: SimulationMode,
+    boundary: Boundary,
+    rule: Rule,
+    larger_than_life: LargerThanLifeRule,
+    cells: Vec<f32>,
+    // Scratch grid the next generation is written into before swapping
+    next_cells: Vec<f32>,
+}
+
+impl CpuSimulation {
+    pub fn new(params: &SimulationParams) -> Self {
+        let (width, height) = (params.width as usize, params.height as usize);
+        Self {
+            generation: 0,
+            width,
+            height,
+            mode: params.mode(),
+            boundary: params.boundary(),
+            rule: params.rule,
+            larger_than_life: params.larger_than_life,
+            cells: vec![0.0; width * height],
+            next_cells: vec![0.0; width * height],
+        }
+    }
+
+    /// The latest generation, in row-major order
+    pub fn cells(&self) -> &[f32] {
+        &self.cells
+    }
+
+    /// Replaces the latest generation with the given cells, in row-major order
+    pub fn set_cells(&mut self, cells: &[f32]) {
+        assert_eq!(cells.len(), self.cells.len(), "Cell grid size mismatch");
+        self.cells.copy_from_slice(cells);
+    }
+
+    /// Overwrites consecutive cells of the latest generation, starting from a cell in row-major order
+    pub fn set_span(&mut self, start: usize, cells: &[f32]) {
+        self.cells[start..start + cells.len()].copy_from_slice(cells);
+    }
+
+    /// State number of a cell, see [`Rule`]
+    fn state(&self, x: isize, y: isize) -> u32 {
+        let cell = match self.boundary.texel(x, y, self.width, self.height) {
+            Some((x, y)) => self.cells[y * self.width + x],
+            None => self.boundary.edge_value(),
+        };
+        cell.round() as u32
+    }
+
+    pub fn step(&mut self) {
+        for y in 0..self.height as isize {
+            for x in 0..self.width as isize {
+                let next = match self.mode {
+                    SimulationMode::LargerThanLife => self.larger_than_life_state(x, y),
+                    _ => self.life_state(x, y),
+                };
+                self.next_cells[y as usize * self.width + x as usize] = next as f32;
+            }
+        }
+
+        std::mem::swap(&mut self.cells, &mut self.next_cells);
+        self.generation += 1;
+    }
+
+    fn life_state(&self, x: isize, y: isize) -> u32 {
+        let neighborhood =
+            NEIGHBORS
+                .iter()
+                .enumerate()
+                .fold(0u8, |neighborhood, (i, &(dx, dy))| {
+                    neighborhood | ((self.state(x + dx, y + dy) == 1) as u8) << i
+                });
+        self.rule.next_state(self.state(x, y), neighborhood)
+    }
+
+    fn larger_than_life_state(&self, x: isize, y: isize) -> u32 {
+        let rule = &self.larger_than_life;
+        let range = rule.range() as i32;
+        let mut count = 0;
+        for dy in -range..=range {
+            let extent = rule.row_extent(dy) as isize;
+            for dx in -extent..=extent {
+                count += (self.state(x + dx, y + dy as isize) == 1) as u32;
+            }
+        }
+
+        let state = self.state(x, y);
+        if !rule.counts_middle() && state == 1 {
+            count -= 1;
+        }
+        rule.next_state(state, count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compute::simulation::{
+            tests::{compute_gpu, grid, run},
+            ComputeBackend,
+        },
+        shared::rng::Rng,
+    };
+
+    fn life(width: u32, height: u32) -> SimulationParams {
+        SimulationParams::new(
+            &winit::dpi::PhysicalSize::new(width, height),
+            SimulationMode::Life,
+        )
+    }
+
+    fn step(params: &SimulationParams, cells: &[f32], steps: usize) -> Vec<f32> {
+        let mut simulation = CpuSimulation::new(params);
+        simulation.set_cells(cells);
+        for _ in 0..steps {
+            simulation.step();
+        }
+        assert_eq!(simulation.generation, steps);
+        simulation.cells().to_vec()
+    }
+
+    #[test]
+    fn blinker_oscillates() {
+        let params = life(5, 5);
+        let horizontal = grid(5, 5, &[(1, 2), (2, 2), (3, 2)]);
+        let vertical = grid(5, 5, &[(2, 1), (2, 2), (2, 3)]);
+        assert_eq!(step(&params, &horizontal, 1), vertical);
+        assert_eq!(step(&params, &horizontal, 2), horizontal);
+    }
+
+    #[test]
+    fn block_is_still() {
+        let params = life(4, 4);
+        let block = grid(4, 4, &[(1, 1), (2, 1), (1, 2), (2, 2)]);
+        assert_eq!(step(&params, &block, 1), block);
+        assert_eq!(step(&params, &block, 10), block);
+    }
+
+    #[test]
+    fn glider_moves_diagonally() {
+        let params = life(10, 10);
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        let moved = glider.map(|(x, y)| (x + 1, y + 1));
+        assert_eq!(
+            step(&params, &grid(10, 10, &glider), 4),
+            grid(10, 10, &moved)
+        );
+        let moved = glider.map(|(x, y)| (x + 5, y + 5));
+        assert_eq!(
+            step(&params, &grid(10, 10, &glider), 20),
+            grid(10, 10, &moved)
+        );
+    }
+
+    /// Random soup compared cell by cell with the GPU after every one of several generations
+    #[tokio::test]
+    async fn matches_gpu() {
+        let Some(gpu) = compute_gpu().await else {
+            return;
+        };
+        let (width, height) = (37, 29);
+        let mut rng = Rng::new(1);
+        let soup: Vec<f32> = (0..width * height)
+            .map(|_| (rng.next_f32() < 0.4) as u8 as f32)
+            .collect();
+        for rule in ["B3/S23", "B36/S23", "B2-a/S12", "B2/S/C4"] {
+            let mut params = life(width, height);
+            params.set_rule(rule).unwrap();
+            for steps in [1, 2, 9] {
+                assert_eq!(
+                    run(&gpu, params, &soup, steps, ComputeBackend::Gpu),
+                    step(&params, &soup, steps),
+                    "{rule} after {steps} generations"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use super::compute_pass::ComputePass;
+use anyhow::{bail, Result};
+
+/// Compute passes stepping a simulation mode on the GPU, built by the mode's module
+pub struct GpuSimulation {
+    pub passes: Vec<ComputePass>,
+    /// Mode-specific state carried from one step to the next outside the ping-pong textures,
+    /// e.g. Physarum agents, saved in snapshots
+    pub state_buffers: Vec<wgpu::Buffer>,
+    /// Mode-specific buffers rebuilt from the parameters or cleared within each step,
+    /// e.g. kernels
+    pub _buffers: Vec<wgpu::Buffer>,
+}
+
+impl GpuSimulation {
+    /// Records every pass of one step, reading the texture at `current`
+    pub fn step(&self, command_encoder: &mut wgpu::CommandEncoder, current: usize) {
+        let mut compute_pass = command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Simulation Pass"),
+        });
+        for pass in &self.passes {
+            pass.dispatch(&mut compute_pass, current);
+        }
+    }
+
+    /// Copies the state buffers into mappable buffers and blocks until their contents are on the CPU
+    pub fn read_state(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Vec<Vec<u8>> {
+        let readback_bufs: Vec<_> = self
+            .state_buffers
+            .iter()
+            .map(|buffer| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("State Readback Buffer"),
+                    size: buffer.size(),
+                    usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                })
+            })
+            .collect();
+        let mut command_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("State Readback Encoder"),
+        });
+        for (buffer, readback_buf) in self.state_buffers.iter().zip(&readback_bufs) {
+            command_encoder.copy_buffer_to_buffer(buffer, 0, readback_buf, 0, buffer.size());
+        }
+        queue.submit(Some(command_encoder.finish()));
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        for readback_buf in &readback_bufs {
+            let sender = sender.clone();
+            readback_buf
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    sender.send(result).unwrap();
+                });
+        }
+        device.poll(wgpu::Maintain::Wait);
+        for _ in &readback_bufs {
+            receiver
+                .recv()
+                .unwrap()
+                .expect("Failed to map state readback buffer");
+        }
+
+        readback_bufs
+            .iter()
+            .map(|readback_buf| {
+                let data = readback_buf.slice(..).get_mapped_range().to_vec();
+                readback_buf.unmap();
+                data
+            })
+            .collect()
+    }
+
+    /// Replaces the contents of the state buffers, as returned by [`GpuSimulation::read_state`]
+    pub fn write_state(&self, queue: &wgpu::Queue, state: &[Vec<u8>]) -> Result<()> {
+        if state.len() != self.state_buffers.len() {
+            bail!(
+                "Expected {} state buffers, found {}",
+                self.state_buffers.len(),
+                state.len()
+            );
+        }
+        for (index, (buffer, data)) in self.state_buffers.iter().zip(state).enumerate() {
+            if data.len() as u64 != buffer.size() {
+                bail!(
+                    "Expected {} bytes in state buffer {index}, found {}",
+                    buffer.size(),
+                    data.len()
+                );
+            }
+        }
+        for (buffer, data) in self.state_buffers.iter().zip(state) {
+            queue.write_buffer(buffer, 0, data);
+        }
+        Ok(())
+    }
+}
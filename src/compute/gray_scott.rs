@@ -0,0 +1,136 @@
+use super::{compute_pass::ComputePass, gpu_simulation::GpuSimulation};
+use crate::shared::{
+    rng::Rng,
+    shader::{self, Shader},
+    sim_params::SimulationParamsBuf,
+    texture::Texture,
+};
+use anyhow::Result;
+
+/// Reacts and diffuses both chemicals of every cell, starting from Pearson's initial condition
+pub fn new(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    textures: &[Texture; 2],
+    sim_params: &SimulationParamsBuf,
+    seed: u64,
+) -> Result<GpuSimulation> {
+    let shader = Shader::new(
+        "gray_scott.wgsl",
+        &[
+            shader::SIM_PARAMS,
+            shader::BOUNDARY,
+            storage_alias(textures[0].texture_format),
+            include_str!("gray_scott.wgsl"),
+        ],
+        device,
+    );
+
+    let params = &sim_params.params;
+    textures[0].write(
+        queue,
+        &initial_state(
+            params.width,
+            params.height,
+            textures[0].texture_format,
+            seed,
+        ),
+    );
+
+    let pass = ComputePass::new(
+        device,
+        "Gray-Scott",
+        &shader,
+        "cs_main",
+        textures,
+        sim_params,
+        &[],
+        ComputePass::texel_workgroups(textures),
+    );
+
+    Ok(GpuSimulation {
+        passes: vec![pass],
+        state_buffers: Vec::new(),
+        _buffers: Vec::new(),
+    })
+}
+
+/// Declares `ChemicalsStorage`, the storage texture `gray_scott.wgsl` writes,
+/// in the format [`SimulationMode::texture_format`] chose for the adapter
+///
+/// [`SimulationMode::texture_format`]: crate::shared::sim_params::SimulationMode::texture_format
+fn storage_alias(format: wgpu::TextureFormat) -> &'static str {
+    match format {
+        wgpu::TextureFormat::Rgba32Float => {
+            "alias ChemicalsStorage = texture_storage_2d<rgba32float, write>;\n"
+        }
+        _ => "alias ChemicalsStorage = texture_storage_2d<rg32float, write>;\n",
+    }
+}
+
+/// Pearson's initial condition: the trivial state U = 1, V = 0 everywhere except a square
+/// in the middle perturbed to U = 1/2, V = 1/4, with 1% noise to break the symmetry.
+/// Any channels of the format after U and V are zero.
+fn initial_state(width: u32, height: u32, format: wgpu::TextureFormat, seed: u64) -> Vec<f32> {
+    let channels = format.block_size(None).unwrap() as usize / std::mem::size_of::<f32>();
+    let mut rng = Rng::new(seed);
+    let (width, height) = (width as usize, height as usize);
+    let half_square = width.min(height) / 26;
+    let (center_x, center_y) = (width / 2, height / 2);
+
+    let mut chemicals = Vec::with_capacity(width * height * channels);
+    for y in 0..height {
+        for x in 0..width {
+            let in_square =
+                x.abs_diff(center_x) < half_square && y.abs_diff(center_y) < half_square;
+            let (u, v) = if in_square { (0.5, 0.25) } else { (1.0, 0.0) };
+            let mut noise = || 1.0 + (rng.next_f32() - 0.5) * 0.02;
+            chemicals.push(u * noise());
+            chemicals.push(v * noise());
+            chemicals.extend(std::iter::repeat_n(0.0, channels - 2));
+        }
+    }
+    chemicals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compute::simulation::tests::compute_gpu,
+        shared::{boundary::tests::assert_reads_across_edges, sim_params::SimulationMode},
+    };
+
+    #[tokio::test]
+    async fn compiles_for_both_storage_formats() {
+        let Some(gpu) = compute_gpu().await else {
+            return;
+        };
+        for format in [
+            wgpu::TextureFormat::Rg32Float,
+            wgpu::TextureFormat::Rgba32Float,
+        ] {
+            gpu.device.push_error_scope(wgpu::ErrorFilter::Validation);
+            Shader::new(
+                "gray_scott.wgsl",
+                &[
+                    shader::SIM_PARAMS,
+                    shader::BOUNDARY,
+                    storage_alias(format),
+                    include_str!("gray_scott.wgsl"),
+                ],
+                &gpu.device,
+            );
+            let error = gpu.device.pop_error_scope().await;
+            assert!(error.is_none(), "{format:?}: {error:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_across_twisted_and_mirrored_edges() {
+        let Some(gpu) = compute_gpu().await else {
+            return;
+        };
+        assert_reads_across_edges(&gpu, SimulationMode::GrayScott, |_| ());
+    }
+}
@@ -0,0 +1,38 @@
+@group(0) @binding(0)
+var chemicals_src: texture_2d<f32>;
+@group(0) @binding(1)
+var chemicals_dst: ChemicalsStorage;
+@group(0) @binding(2)
+var<uniform> params: SimulationParams;
+
+fn chemicals(pos: vec2<i32>) -> vec2<f32> {
+    return load_cell(chemicals_src, pos).rg;
+}
+
+@compute @workgroup_size(8, 8)
+fn cs_main(
+    @builtin(global_invocation_id) id: vec3<u32>,
+) {
+    if id.x >= params.width || id.y >= params.height {
+        return;
+    }
+    let pos = vec2<i32>(id.xy);
+    let p = params.gray_scott;
+
+    // Five point Laplacian of both chemicals
+    let center = chemicals(pos);
+    let laplacian = chemicals(pos + vec2<i32>(1, 0))
+        + chemicals(pos + vec2<i32>(-1, 0))
+        + chemicals(pos + vec2<i32>(0, 1))
+        + chemicals(pos + vec2<i32>(0, -1))
+        - 4.0 * center;
+
+    let u = center.x;
+    let v = center.y;
+    let reaction = u * v * v;
+    let du = p.diffusion_u * laplacian.x - reaction + p.feed * (1.0 - u);
+    let dv = p.diffusion_v * laplacian.y + reaction - (p.feed + p.kill) * v;
+
+    let next = center + p.dt * vec2<f32>(du, dv);
+    textureStore(chemicals_dst, pos, vec4<f32>(next, 0.0, 1.0));
+}
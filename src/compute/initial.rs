@@ -0,0 +1,351 @@
+use crate::shared::{rng::Rng, sim_params::SimulationMode};
+use anyhow::{bail, Result};
+use std::str::FromStr;
+
+/// Symmetry group of a soup, around the centre of its square
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Symmetry {
+    /// Unchanged by a half turn
+    C2,
+    /// Unchanged by quarter turns
+    C4,
+    /// Unchanged by quarter turns and reflections
+    D8,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum InitialKind {
+    /// The mode's own start: empty for Life, the creature for Lenia,
+    /// Pearson's square for Gray-Scott and Rafler's squares for SmoothLife
+    #[default]
+    Default,
+    Empty,
+    /// Cells alive with probability `density` over the whole grid
+    Random,
+    /// Cells alive with probability `density` in a centred box, `extent` of the grid wide and high
+    Box,
+    /// Fractal value noise with features `scale` cells wide
+    Noise,
+    /// Cells alive with probability `density` in a centred square, `extent` of the grid's
+    /// shorter side wide, made symmetric
+    Soup(Symmetry),
+}
+
+/// Generator of the first generation, reproducible from the seed alone.
+/// States are generated on the CPU and uploaded, so both backends start from the same cells
+#[derive(Copy, Clone, Debug)]
+pub struct InitialCondition {
+    pub kind: InitialKind,
+    /// Fraction of cells alive, or of the noise below which cells are alive in discrete modes
+    pub density: f32,
+    /// Fraction of the grid covered by boxes and soups
+    pub extent: f32,
+    /// Width in cells of the coarsest noise features
+    pub scale: f32,
+}
+
+/// Octaves of noise summed, each twice as fine and half as strong as the last
+const NOISE_OCTAVES: u32 = 4;
+
+impl InitialCondition {
+    /// Texels of the first generation in row-major order, or `None` to keep the mode's own start
+    pub fn cells(
+        &self,
+        mode: SimulationMode,
+        width: u32,
+        height: u32,
+        channels: usize,
+        seed: u64,
+    ) -> Result<Option<Vec<f32>>> {
+        if !(0.0..=1.0).contains(&self.density) {
+            bail!("Density must be within 0 and 1, found {}", self.density);
+        }
+        if !(self.extent > 0.0 && self.extent <= 1.0) {
+            bail!(
+                "Extent must be above 0 and at most 1, found {}",
+                self.extent
+            );
+        }
+        if !(self.scale > 0.0 && self.scale.is_finite()) {
+            bail!("Noise scale must be above 0 cells, found {}", self.scale);
+        }
+
+        let (width, height) = (width as usize, height as usize);
+        let mut rng = Rng::new(seed);
+        let mut alive = vec![0.0; width * height];
+        match self.kind {
+            InitialKind::Default => return Ok(None),
+            InitialKind::Empty => {}
+            InitialKind::Random => {
+                self.fill(&mut rng, &mut alive, width, [0, 0], [width, height]);
+            }
+            InitialKind::Box => {
+                let size = [
+                    (width as f32 * self.extent).round().max(1.0) as usize,
+                    (height as f32 * self.extent).round().max(1.0) as usize,
+                ];
+                let origin = [(width - size[0]) / 2, (height - size[1]) / 2];
+                self.fill(&mut rng, &mut alive, width, origin, size);
+            }
+            InitialKind::Noise => {
+                let discrete =
+                    matches!(mode, SimulationMode::Life | SimulationMode::LargerThanLife);
+                for y in 0..height {
+                    for x in 0..width {
+                        let noise = fractal_noise(x as f32, y as f32, self.scale, seed);
+                        alive[y * width + x] = if discrete {
+                            (noise < self.density) as u8 as f32
+                        } else {
+                            noise
+                        };
+                    }
+                }
+            }
+            InitialKind::Soup(symmetry) => {
+                let side = (width.min(height) as f32 * self.extent).round().max(1.0) as usize;
+                let mut square = vec![0.0; side * side];
+                self.fill(&mut rng, &mut square, side, [0, 0], [side, side]);
+                let (left, top) = ((width - side) / 2, (height - side) / 2);
+                for y in 0..side {
+                    for x in 0..side {
+                        let [source_x, source_y] = symmetry.representative([x, y], side);
+                        alive[(top + y) * width + left + x] = square[source_y * side + source_x];
+                    }
+                }
+            }
+        }
+
+        let mut cells = Vec::with_capacity(alive.len() * channels);
+        for value in alive {
+            let texel = mode.blended_cell(value);
+            cells.extend(texel.iter().chain(std::iter::repeat(&0.0)).take(channels));
+        }
+        Ok(Some(cells))
+    }
+
+    /// Sets cells of a `size` box at `origin` alive with probability `density`,
+    /// drawing one number per cell of the box in row-major order
+    fn fill(
+        &self,
+        rng: &mut Rng,
+        cells: &mut [f32],
+        width: usize,
+        origin: [usize; 2],
+        size: [usize; 2],
+    ) {
+        for y in origin[1]..origin[1] + size[1] {
+            for x in origin[0]..origin[0] + size[0] {
+                cells[y * width + x] = (rng.next_f32() < self.density) as u8 as f32;
+            }
+        }
+    }
+}
+
+impl Symmetry {
+    /// The cell of the square whose state is copied to `cell`: the least in row-major order
+    /// of the cells `cell` is mapped to by the group
+    fn representative(&self, cell: [usize; 2], side: usize) -> [usize; 2] {
+        let last = side - 1;
+        let half_turn = |[x, y]: [usize; 2]| [last - x, last - y];
+        let quarter_turn = |[x, y]: [usize; 2]| [last - y, x];
+        let transpose = |[x, y]: [usize; 2]| [y, x];
+        let orbit: Vec<[usize; 2]> = match self {
+            Self::C2 => vec![cell, half_turn(cell)],
+            Self::C4 => std::iter::successors(Some(cell), |&cell| Some(quarter_turn(cell)))
+                .take(4)
+                .collect(),
+            Self::D8 => std::iter::successors(Some(cell), |&cell| Some(quarter_turn(cell)))
+                .take(4)
+                .flat_map(|cell| [cell, transpose(cell)])
+                .collect(),
+        };
+        orbit.into_iter().min_by_key(|&[x, y]| (y, x)).unwrap()
+    }
+}
+
+/// Value noise summed over octaves, in [0, 1]
+fn fractal_noise(x: f32, y: f32, scale: f32, seed: u64) -> f32 {
+    let (mut sum, mut total_amplitude) = (0.0, 0.0);
+    let (mut frequency, mut amplitude) = (1.0 / scale, 1.0);
+    for octave in 0..NOISE_OCTAVES {
+        sum += amplitude * value_noise(x * frequency, y * frequency, seed, octave);
+        total_amplitude += amplitude;
+        frequency *= 2.0;
+        amplitude *= 0.5;
+    }
+    sum / total_amplitude
+}
+
+/// Random values on the integer lattice, smoothly interpolated between
+fn value_noise(x: f32, y: f32, seed: u64, octave: u32) -> f32 {
+    let lattice = |x: i64, y: i64| {
+        // Each lattice point seeds its own generator, so noise doesn't depend on visiting order
+        let point = (x as u64 & 0xffff_ffff) | (y as u64) << 32;
+        Rng::new(seed ^ point.rotate_left(octave * 8)).next_f32()
+    };
+    let (x0, y0) = (x.floor(), y.floor());
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (tx, ty) = (smooth(x - x0), smooth(y - y0));
+    let (x0, y0) = (x0 as i64, y0 as i64);
+    let top = lattice(x0, y0) + (lattice(x0 + 1, y0) - lattice(x0, y0)) * tx;
+    let bottom = lattice(x0, y0 + 1) + (lattice(x0 + 1, y0 + 1) - lattice(x0, y0 + 1)) * tx;
+    top + (bottom - top) * ty
+}
+
+impl FromStr for InitialKind {
+    type Err = anyhow::Error;
+
+    fn from_str(kind: &str) -> Result<Self> {
+        Ok(match kind.to_ascii_lowercase().as_str() {
+            "default" => Self::Default,
+            "empty" => Self::Empty,
+            "random" => Self::Random,
+            "box" => Self::Box,
+            "noise" => Self::Noise,
+            "soup-c2" => Self::Soup(Symmetry::C2),
+            "soup-c4" => Self::Soup(Symmetry::C4),
+            "soup-d8" => Self::Soup(Symmetry::D8),
+            _ => bail!(
+                "Unknown initial condition '{kind}', \
+                expected default, empty, random, box, noise, soup-c2, soup-c4 or soup-d8"
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: usize = 40;
+    const HEIGHT: usize = 30;
+
+    fn condition(kind: InitialKind, extent: f32) -> InitialCondition {
+        InitialCondition {
+            kind,
+            density: 0.5,
+            extent,
+            scale: 8.0,
+        }
+    }
+
+    /// Bits of the single channel cells of a Life grid, so equal cells compare byte for byte
+    fn bits(condition: InitialCondition, mode: SimulationMode, seed: u64) -> Vec<u32> {
+        condition
+            .cells(mode, WIDTH as u32, HEIGHT as u32, 1, seed)
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(f32::to_bits)
+            .collect()
+    }
+
+    #[test]
+    fn seeds_reproduce_their_cells() {
+        for kind in [
+            InitialKind::Random,
+            InitialKind::Box,
+            InitialKind::Noise,
+            InitialKind::Soup(Symmetry::C2),
+            InitialKind::Soup(Symmetry::C4),
+            InitialKind::Soup(Symmetry::D8),
+        ] {
+            for mode in [SimulationMode::Life, SimulationMode::Lenia] {
+                let condition = condition(kind, 0.5);
+                let cells = bits(condition, mode, 7);
+                assert_eq!(bits(condition, mode, 7), cells, "{kind:?} {mode:?}");
+                assert_ne!(bits(condition, mode, 8), cells, "{kind:?} {mode:?}");
+                assert_ne!(bits(condition, mode, 7 << 32), cells, "{kind:?} {mode:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn boxes_stay_centred() {
+        let mut condition = condition(InitialKind::Box, 0.5);
+        condition.density = 1.0;
+        let cells = bits(condition, SimulationMode::Life, 0);
+        let alive = |x: usize, y: usize| cells[y * WIDTH + x] == 1.0_f32.to_bits();
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let inside = (10..30).contains(&x) && (7..22).contains(&y);
+                assert_eq!(alive(x, y), inside, "({x}, {y})");
+            }
+        }
+        condition.density = 0.0;
+        assert!(bits(condition, SimulationMode::Life, 0)
+            .iter()
+            .all(|&cell| cell == 0));
+    }
+
+    /// Cells of the centred square of a soup, and the side of the square
+    fn soup(symmetry: Symmetry, extent: f32, seed: u64) -> (Vec<Vec<u32>>, usize) {
+        let cells = bits(
+            condition(InitialKind::Soup(symmetry), extent),
+            SimulationMode::Life,
+            seed,
+        );
+        let side = (HEIGHT as f32 * extent).round() as usize;
+        let (left, top) = ((WIDTH - side) / 2, (HEIGHT - side) / 2);
+        let rows: Vec<_> = cells.chunks(WIDTH).collect();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, &cell) in row.iter().enumerate() {
+                let inside = (left..left + side).contains(&x) && (top..top + side).contains(&y);
+                assert!(inside || cell == 0, "({x}, {y}) is outside the soup");
+            }
+        }
+        let square = rows[top..top + side]
+            .iter()
+            .map(|row| row[left..left + side].to_vec())
+            .collect();
+        (square, side)
+    }
+
+    /// The square with `cell` read from where `map` sends it
+    fn mapped(square: &[Vec<u32>], map: impl Fn([usize; 2]) -> [usize; 2]) -> Vec<Vec<u32>> {
+        (0..square.len())
+            .map(|y| {
+                (0..square.len())
+                    .map(|x| {
+                        let [x, y] = map([x, y]);
+                        square[y][x]
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn soups_have_their_symmetry() {
+        // Squares of odd and even sides, with and without a centre cell
+        for extent in [0.5, 0.4] {
+            for seed in 0..4 {
+                let check = |symmetry: Symmetry| {
+                    let (square, side) = soup(symmetry, extent, seed);
+                    let last = side - 1;
+                    let half_turn = mapped(&square, |[x, y]| [last - x, last - y]);
+                    let quarter_turn = mapped(&square, |[x, y]| [last - y, x]);
+                    let transpose = mapped(&square, |[x, y]| [y, x]);
+                    let mirror = mapped(&square, |[x, y]| [last - x, y]);
+                    (square, [half_turn, quarter_turn, transpose, mirror])
+                };
+
+                let (square, [half_turn, quarter_turn, transpose, _]) = check(Symmetry::C2);
+                assert_eq!(half_turn, square);
+                assert_ne!(quarter_turn, square);
+                assert_ne!(transpose, square);
+
+                let (square, [half_turn, quarter_turn, transpose, mirror]) = check(Symmetry::C4);
+                assert_eq!(half_turn, square);
+                assert_eq!(quarter_turn, square);
+                assert_ne!(transpose, square);
+                assert_ne!(mirror, square);
+
+                let (square, maps) = check(Symmetry::D8);
+                for map in maps {
+                    assert_eq!(map, square);
+                }
+            }
+        }
+    }
+}
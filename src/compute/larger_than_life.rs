@@ -0,0 +1,115 @@
+use super::{compute_pass::ComputePass, gpu_simulation::GpuSimulation};
+use crate::shared::{
+    shader::{self, Shader},
+    sim_params::SimulationParamsBuf,
+    texture::Texture,
+};
+use anyhow::{bail, Result};
+
+/// Side of the square of cells each workgroup computes, must match `larger_than_life.wgsl`
+const TILE_SIZE: u32 = 16;
+/// Cells of workgroup memory holding a band of rows of the tile and its apron,
+/// must match `larger_than_life.wgsl`
+const BAND_CELLS: u32 = 4096;
+
+/// Counts the alive cells in every neighborhood from running counts along the rows of a tile
+/// in workgroup memory, so each cell reads one count per row of its neighborhood.
+/// Wide neighborhoods are loaded band by band of rows
+pub fn new(
+    device: &wgpu::Device,
+    textures: &[Texture; 2],
+    sim_params: &SimulationParamsBuf,
+) -> Result<GpuSimulation> {
+    let params = &sim_params.params;
+    let range = params.larger_than_life.range();
+    if TILE_SIZE + 2 * range > BAND_CELLS {
+        bail!("Larger than Life range {range} is too wide for a row of workgroup memory");
+    }
+
+    let shader = Shader::new(
+        "larger_than_life.wgsl",
+        &[
+            shader::SIM_PARAMS,
+            shader::BOUNDARY,
+            include_str!("larger_than_life.wgsl"),
+        ],
+        device,
+    );
+
+    let pass = ComputePass::new(
+        device,
+        "Larger than Life",
+        &shader,
+        "cs_main",
+        textures,
+        sim_params,
+        &[],
+        (
+            params.width.div_ceil(TILE_SIZE),
+            params.height.div_ceil(TILE_SIZE),
+            1,
+        ),
+    );
+
+    Ok(GpuSimulation {
+        passes: vec![pass],
+        state_buffers: Vec::new(),
+        _buffers: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        compute::{
+            cpu_simulation::CpuSimulation,
+            simulation::{
+                tests::{compute_gpu, run},
+                ComputeBackend,
+            },
+        },
+        shared::{
+            rng::Rng,
+            sim_params::{SimulationMode, SimulationParams},
+        },
+    };
+
+    /// Random soups stepped under every neighborhood, with ranges needing one band
+    /// of workgroup memory and several, compared cell by cell with the CPU
+    #[tokio::test]
+    async fn matches_cpu() {
+        let Some(gpu) = compute_gpu().await else {
+            return;
+        };
+        let (width, height) = (35, 29);
+        let mut rng = Rng::new(7);
+        let soup: Vec<f32> = (0..width * height)
+            .map(|_| (rng.next_f32() < 0.5) as u8 as f32)
+            .collect();
+        for rule in [
+            "R2,C0,M1,S5..9,B5..7,NM",
+            "R3,C0,M0,S4..8,B5..6,NN",
+            "R5,C0,M1,S34..58,B34..45,NM",
+            "R7,C3,M0,S40..80,B45..70,NC",
+            "R12,C0,M1,S150..300,B170..260,NC",
+            "R30,C0,M0,S1000..2500,B1200..2000,NM",
+            "R40,C4,M1,S800..1800,B900..1500,NN",
+        ] {
+            let mut params = SimulationParams::new(
+                &winit::dpi::PhysicalSize::new(width, height),
+                SimulationMode::LargerThanLife,
+            );
+            params.set_rule(rule).unwrap();
+            let mut cpu_simulation = CpuSimulation::new(&params);
+            cpu_simulation.set_cells(&soup);
+            for steps in 1..=3 {
+                cpu_simulation.step();
+                assert_eq!(
+                    run(&gpu, params, &soup, steps, ComputeBackend::Gpu),
+                    cpu_simulation.cells(),
+                    "{rule} after {steps} generations"
+                );
+            }
+        }
+    }
+}
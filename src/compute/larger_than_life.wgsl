@@ -0,0 +1,123 @@
+@group(0) @binding(0)
+var cells_src: texture_2d<f32>;
+@group(0) @binding(1)
+var cells_dst: texture_storage_2d<r32float, write>;
+@group(0) @binding(2)
+var<uniform> params: SimulationParams;
+
+// Each workgroup computes a TILE_SIZE square of cells from the alive cells of that square and
+// an apron of `range` cells around it, loaded into workgroup memory in bands of whole rows
+// so neighborhoods of any range are counted from the same memory.
+// Must match TILE_SIZE and BAND_CELLS in larger_than_life.rs
+const TILE_SIZE = 16u;
+const BAND_CELLS = 4096u;
+
+// Running count of alive cells along each row of a band of the tile and apron, row-major
+var<workgroup> row_counts: array<u32, BAND_CELLS>;
+
+fn cell(pos: vec2<i32>) -> f32 {
+    return load_cell(cells_src, pos).r;
+}
+
+// Cells hold their state number: 0 is dead, 1 is alive, anything above is dying
+fn state(pos: vec2<i32>) -> u32 {
+    return u32(cell(pos) + 0.5);
+}
+
+// Half-width of the neighborhood in the row dy away from the cell,
+// matching LargerThanLifeRule::row_extent
+fn row_extent(dy: i32) -> i32 {
+    let range = i32(params.larger_than_life.range);
+    let neighborhood = params.larger_than_life.neighborhood;
+    if neighborhood == NEIGHBORHOOD_VON_NEUMANN {
+        return range - abs(dy);
+    }
+    if neighborhood == NEIGHBORHOOD_CIRCULAR {
+        // Largest extent with extent^2 + dy^2 < (range + 1/2)^2, in integers
+        let limit = range * range + range - dy * dy;
+        var extent = i32(sqrt(f32(max(limit, 0))));
+        while (extent + 1) * (extent + 1) <= limit {
+            extent++;
+        }
+        while extent >= 0 && extent * extent > limit {
+            extent--;
+        }
+        return extent;
+    }
+    return range;
+}
+
+fn next_state(state: u32, count: u32) -> u32 {
+    let rule = params.larger_than_life;
+    if state == 0u {
+        return u32(count >= rule.birth.x && count <= rule.birth.y);
+    }
+    if state == 1u && count >= rule.survival.x && count <= rule.survival.y {
+        return 1u;
+    }
+    // Failing to survive starts dying, and the last dying state dies
+    return (state + 1u) % rule.states;
+}
+
+@compute @workgroup_size(16, 16)
+fn cs_main(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_id) local_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+) {
+    let range = params.larger_than_life.range;
+    let side = TILE_SIZE + 2u * range;
+    // Ranges up to 24 fit the whole tile and apron in a single band
+    let band_rows = BAND_CELLS / side;
+    let origin = vec2<i32>(workgroup_id.xy * TILE_SIZE) - i32(range);
+    let center = vec2<i32>(local_id.xy) + i32(range);
+    var count = 0u;
+
+    for (var band = 0u; band < side; band += band_rows) {
+        let rows = min(band_rows, side - band);
+
+        // Every invocation loads its share of the band
+        for (var i = local_index; i < rows * side; i += TILE_SIZE * TILE_SIZE) {
+            let pos = origin + vec2<i32>(vec2<u32>(i % side, band + i / side));
+            row_counts[i] = u32(state(pos) == 1u);
+        }
+        workgroupBarrier();
+
+        // Then one invocation per row turns it into running counts
+        for (var row = local_index; row < rows; row += TILE_SIZE * TILE_SIZE) {
+            for (var column = 1u; column < side; column++) {
+                row_counts[row * side + column] += row_counts[row * side + column - 1u];
+            }
+        }
+        workgroupBarrier();
+
+        // Sum the span of every row of the neighborhood within the band from the running counts
+        let first = max(-i32(range), i32(band) - center.y);
+        let last = min(i32(range), i32(band + rows) - 1 - center.y);
+        for (var dy = first; dy <= last; dy++) {
+            let extent = row_extent(dy);
+            if extent < 0 {
+                continue;
+            }
+            let row = u32(center.y + dy - i32(band)) * side;
+            count += row_counts[row + u32(center.x + extent)];
+            if center.x - extent > 0 {
+                count -= row_counts[row + u32(center.x - extent - 1)];
+            }
+        }
+        // Every invocation has read the band before the next one overwrites it
+        workgroupBarrier();
+    }
+
+    if id.x >= params.width || id.y >= params.height {
+        return;
+    }
+
+    let pos = vec2<i32>(id.xy);
+    let current = state(pos);
+    if params.larger_than_life.middle == 0u && current == 1u {
+        count -= 1u;
+    }
+    textureStore(cells_dst, pos, vec4<f32>(f32(next_state(current, count)), 0.0, 0.0, 1.0));
+}
@@ -0,0 +1,218 @@
+use super::{compute_pass::ComputePass, gpu_simulation::GpuSimulation};
+use crate::shared::{
+    shader::{self, Shader},
+    sim_params::{LeniaParams, SimulationParamsBuf},
+    texture::Texture,
+};
+use anyhow::{anyhow, bail, Result};
+use wgpu::util::DeviceExt;
+
+/// Convolves the state with the kernel and grows every cell by the growth function
+pub fn new(
+    device: &wgpu::Device,
+    textures: &[Texture; 2],
+    sim_params: &SimulationParamsBuf,
+) -> Result<GpuSimulation> {
+    let shader = Shader::new(
+        "lenia.wgsl",
+        &[
+            shader::SIM_PARAMS,
+            shader::BOUNDARY,
+            include_str!("lenia.wgsl"),
+        ],
+        device,
+    );
+
+    let side = 2 * u64::from(sim_params.params.lenia.radius) + 1;
+    let kernel_size = side * side * std::mem::size_of::<f32>() as u64;
+    let max_binding_size = u64::from(device.limits().max_storage_buffer_binding_size);
+    if kernel_size > max_binding_size {
+        bail!(
+            "Lenia kernel of radius {} needs {kernel_size} bytes, \
+            more than the adapter's storage binding limit of {max_binding_size}",
+            sim_params.params.lenia.radius
+        );
+    }
+    let kernel = kernel(&sim_params.params.lenia)?;
+    let kernel_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Lenia Kernel Buffer"),
+        contents: bytemuck::cast_slice(&kernel),
+        usage: wgpu::BufferUsages::STORAGE,
+    });
+
+    let pass = ComputePass::new(
+        device,
+        "Lenia",
+        &shader,
+        "cs_main",
+        textures,
+        sim_params,
+        &[(
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            kernel_buf.as_entire_binding(),
+        )],
+        ComputePass::texel_workgroups(textures),
+    );
+
+    Ok(GpuSimulation {
+        passes: vec![pass],
+        state_buffers: Vec::new(),
+        _buffers: vec![kernel_buf],
+    })
+}
+
+/// Weights of the `(2 * radius + 1)` square kernel in row-major order, summing to one.
+/// Each peak is a shell of the smooth bump `exp(4 - 1 / (r * (1 - r)))`, which vanishes
+/// at the cells of kernels too small or with peaks too low to hold any weight
+fn kernel(params: &LeniaParams) -> Result<Vec<f32>> {
+    let radius = i64::from(params.radius);
+    let peaks = params.peaks();
+    let mut weights: Vec<f32> = (-radius..=radius)
+        .flat_map(|dy| (-radius..=radius).map(move |dx| (dx, dy)))
+        .map(|(dx, dy)| {
+            let distance = ((dx * dx + dy * dy) as f32).sqrt() / radius as f32;
+            if distance >= 1.0 {
+                return 0.0;
+            }
+            let shells = distance * peaks.len() as f32;
+            let r = shells.fract();
+            let core = if r > 0.0 {
+                (4.0 - 1.0 / (r * (1.0 - r))).exp()
+            } else {
+                0.0
+            };
+            peaks[shells as usize] * core
+        })
+        .collect();
+
+    let total: f32 = weights.iter().sum();
+    if total <= 0.0 {
+        bail!(
+            "Lenia kernel of radius {} with peaks {:?} has no weight, \
+            increase the radius or the peaks",
+            params.radius,
+            peaks
+        );
+    }
+    weights.iter_mut().for_each(|weight| *weight /= total);
+    Ok(weights)
+}
+
+/// A known pattern and the parameters it lives in
+pub struct Creature {
+    pub name: &'static str,
+    pub radius: u32,
+    pub mu: f32,
+    pub sigma: f32,
+    pub dt: f32,
+    pub peaks: &'static [f32],
+    /// Rows of cell states, at the scale of `radius`
+    pub cells: &'static [&'static [f32]],
+}
+
+pub const CREATURES: &[Creature] = &[ORBIUM];
+
+/// Orbium unicaudatus, from Bert Chan's Lenia
+#[rustfmt::skip]
+const ORBIUM: Creature = Creature {
+    name: "orbium",
+    radius: 13,
+    mu: 0.15,
+    sigma: 0.015,
+    dt: 0.1,
+    peaks: &[1.0],
+    cells: &[
+        &[0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.1, 0.14, 0.1, 0.0, 0.0, 0.03, 0.03, 0.0, 0.0, 0.3, 0.0, 0.0, 0.0, 0.0],
+        &[0.0, 0.0, 0.0, 0.0, 0.0, 0.08, 0.24, 0.3, 0.3, 0.18, 0.14, 0.15, 0.16, 0.15, 0.09, 0.2, 0.0, 0.0, 0.0, 0.0],
+        &[0.0, 0.0, 0.0, 0.0, 0.0, 0.15, 0.34, 0.44, 0.46, 0.38, 0.18, 0.14, 0.11, 0.13, 0.19, 0.18, 0.45, 0.0, 0.0, 0.0],
+        &[0.0, 0.0, 0.0, 0.0, 0.06, 0.13, 0.39, 0.5, 0.5, 0.37, 0.06, 0.0, 0.0, 0.0, 0.02, 0.16, 0.68, 0.0, 0.0, 0.0],
+        &[0.0, 0.0, 0.0, 0.11, 0.17, 0.17, 0.33, 0.4, 0.38, 0.28, 0.14, 0.0, 0.0, 0.0, 0.0, 0.0, 0.18, 0.42, 0.0, 0.0],
+        &[0.0, 0.0, 0.09, 0.18, 0.13, 0.06, 0.08, 0.26, 0.32, 0.32, 0.27, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.82, 0.0, 0.0],
+        &[0.27, 0.0, 0.16, 0.12, 0.0, 0.0, 0.0, 0.25, 0.38, 0.44, 0.45, 0.34, 0.0, 0.0, 0.0, 0.0, 0.0, 0.22, 0.17, 0.0],
+        &[0.0, 0.07, 0.2, 0.02, 0.0, 0.0, 0.0, 0.31, 0.48, 0.57, 0.6, 0.57, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.49, 0.0],
+        &[0.0, 0.59, 0.19, 0.0, 0.0, 0.0, 0.0, 0.2, 0.57, 0.69, 0.76, 0.76, 0.49, 0.0, 0.0, 0.0, 0.0, 0.0, 0.36, 0.0],
+        &[0.0, 0.58, 0.19, 0.0, 0.0, 0.0, 0.0, 0.0, 0.67, 0.83, 0.9, 0.92, 0.87, 0.12, 0.0, 0.0, 0.0, 0.0, 0.22, 0.07],
+        &[0.0, 0.0, 0.46, 0.0, 0.0, 0.0, 0.0, 0.0, 0.7, 0.93, 1.0, 1.0, 1.0, 0.61, 0.0, 0.0, 0.0, 0.0, 0.18, 0.11],
+        &[0.0, 0.0, 0.82, 0.0, 0.0, 0.0, 0.0, 0.0, 0.47, 1.0, 1.0, 0.98, 1.0, 0.96, 0.27, 0.0, 0.0, 0.0, 0.19, 0.1],
+        &[0.0, 0.0, 0.46, 0.0, 0.0, 0.0, 0.0, 0.0, 0.25, 1.0, 1.0, 0.84, 0.92, 0.97, 0.54, 0.14, 0.04, 0.1, 0.21, 0.05],
+        &[0.0, 0.0, 0.0, 0.4, 0.0, 0.0, 0.0, 0.0, 0.09, 0.8, 1.0, 0.82, 0.8, 0.85, 0.63, 0.31, 0.18, 0.19, 0.2, 0.01],
+        &[0.0, 0.0, 0.0, 0.36, 0.1, 0.0, 0.0, 0.0, 0.05, 0.54, 0.86, 0.79, 0.74, 0.72, 0.6, 0.39, 0.28, 0.24, 0.13, 0.0],
+        &[0.0, 0.0, 0.0, 0.01, 0.3, 0.07, 0.0, 0.0, 0.08, 0.36, 0.64, 0.7, 0.64, 0.6, 0.51, 0.39, 0.29, 0.19, 0.04, 0.0],
+        &[0.0, 0.0, 0.0, 0.0, 0.1, 0.24, 0.14, 0.1, 0.15, 0.29, 0.45, 0.53, 0.52, 0.46, 0.4, 0.31, 0.21, 0.08, 0.0, 0.0],
+        &[0.0, 0.0, 0.0, 0.0, 0.0, 0.08, 0.21, 0.21, 0.22, 0.29, 0.36, 0.39, 0.37, 0.33, 0.26, 0.18, 0.09, 0.0, 0.0, 0.0],
+        &[0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.03, 0.13, 0.19, 0.22, 0.24, 0.24, 0.23, 0.18, 0.13, 0.05, 0.0, 0.0, 0.0, 0.0],
+        &[0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.02, 0.06, 0.08, 0.09, 0.07, 0.05, 0.01, 0.0, 0.0, 0.0, 0.0, 0.0],
+    ],
+};
+
+impl Creature {
+    pub fn find(name: &str) -> Result<&'static Self> {
+        CREATURES
+            .iter()
+            .find(|creature| creature.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                let known: Vec<_> = CREATURES.iter().map(|creature| creature.name).collect();
+                anyhow!(
+                    "Unknown Lenia creature '{name}', expected one of {}",
+                    known.join(", ")
+                )
+            })
+    }
+
+    pub fn params(&self) -> Result<LeniaParams> {
+        LeniaParams::new(self.radius, self.mu, self.sigma, self.dt, self.peaks)
+    }
+
+    /// A grid of the given size with the creature in the middle, in row-major order
+    pub fn stamp(&self, width: u32, height: u32) -> Vec<f32> {
+        let (width, height) = (width as usize, height as usize);
+        let mut cells = vec![0.0; width * height];
+        let top = height.saturating_sub(self.cells.len()) / 2;
+        let left = width.saturating_sub(self.cells[0].len()) / 2;
+        for (y, row) in self.cells.iter().enumerate().take(height) {
+            for (x, &cell) in row.iter().enumerate().take(width) {
+                cells[(top + y) * width + left + x] = cell;
+            }
+        }
+        cells
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compute::simulation::tests::compute_gpu,
+        shared::{boundary::tests::assert_reads_across_edges, sim_params::SimulationMode},
+    };
+
+    #[test]
+    fn kernels_sum_to_one_up_to_the_largest_radius() {
+        for radius in [3, 13, LeniaParams::MAX_RADIUS] {
+            let params = LeniaParams::new(radius, 0.15, 0.015, 0.1, &[1.0, 0.5]).unwrap();
+            let weights = kernel(&params).unwrap();
+            let side = 2 * radius as usize + 1;
+            assert_eq!(weights.len(), side * side);
+            let total: f64 = weights.iter().map(|&weight| f64::from(weight)).sum();
+            assert!(
+                (total - 1.0).abs() < 1e-3,
+                "radius {radius} sums to {total}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_across_twisted_and_mirrored_edges() {
+        let Some(gpu) = compute_gpu().await else {
+            return;
+        };
+        // A kernel reaching across the edge but well within the grid
+        assert_reads_across_edges(&gpu, SimulationMode::Lenia, |params| {
+            params.lenia = LeniaParams::new(4, 0.3, 0.1, 0.5, &[1.0]).unwrap();
+        });
+    }
+}
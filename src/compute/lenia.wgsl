@@ -0,0 +1,44 @@
+@group(0) @binding(0)
+var cells_src: texture_2d<f32>;
+@group(0) @binding(1)
+var cells_dst: texture_storage_2d<r32float, write>;
+@group(0) @binding(2)
+var<uniform> params: SimulationParams;
+// Square of (2 * radius + 1) weights in row-major order, precomputed on the CPU
+@group(0) @binding(3)
+var<storage, read> kernel: array<f32>;
+
+fn cell(pos: vec2<i32>) -> f32 {
+    return load_cell(cells_src, pos).r;
+}
+
+fn growth(potential: f32) -> f32 {
+    let p = params.lenia;
+    let d = (potential - p.mu) / p.sigma;
+    return 2.0 * exp(-0.5 * d * d) - 1.0;
+}
+
+@compute @workgroup_size(8, 8)
+fn cs_main(
+    @builtin(global_invocation_id) id: vec3<u32>,
+) {
+    if id.x >= params.width || id.y >= params.height {
+        return;
+    }
+    let pos = vec2<i32>(id.xy);
+    let radius = i32(params.lenia.radius);
+    let diameter = 2 * radius + 1;
+
+    var potential = 0.0;
+    for (var dy = -radius; dy <= radius; dy++) {
+        for (var dx = -radius; dx <= radius; dx++) {
+            let weight = kernel[(dy + radius) * diameter + dx + radius];
+            if weight > 0.0 {
+                potential += weight * cell(pos + vec2<i32>(dx, dy));
+            }
+        }
+    }
+
+    let next = clamp(cell(pos) + params.lenia.dt * growth(potential), 0.0, 1.0);
+    textureStore(cells_dst, pos, vec4<f32>(next, 0.0, 0.0, 1.0));
+}
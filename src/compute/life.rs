@@ -0,0 +1,41 @@
+use super::{compute_pass::ComputePass, gpu_simulation::GpuSimulation};
+use crate::shared::{
+    shader::{self, Shader},
+    sim_params::SimulationParamsBuf,
+    texture::Texture,
+};
+use anyhow::Result;
+
+/// Applies the life-like rule to every cell
+pub fn new(
+    device: &wgpu::Device,
+    textures: &[Texture; 2],
+    sim_params: &SimulationParamsBuf,
+) -> Result<GpuSimulation> {
+    let shader = Shader::new(
+        "life.wgsl",
+        &[
+            shader::SIM_PARAMS,
+            shader::BOUNDARY,
+            include_str!("life.wgsl"),
+        ],
+        device,
+    );
+
+    let pass = ComputePass::new(
+        device,
+        "Life",
+        &shader,
+        "cs_main",
+        textures,
+        sim_params,
+        &[],
+        ComputePass::texel_workgroups(textures),
+    );
+
+    Ok(GpuSimulation {
+        passes: vec![pass],
+        state_buffers: Vec::new(),
+        _buffers: Vec::new(),
+    })
+}
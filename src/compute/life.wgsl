@@ -0,0 +1,64 @@
+@group(0) @binding(0)
+var cells_src: texture_2d<f32>;
+@group(0) @binding(1)
+var cells_dst: texture_storage_2d<r32float, write>;
+@group(0) @binding(2)
+var<uniform> params: SimulationParams;
+
+fn cell(pos: vec2<i32>) -> f32 {
+    return load_cell(cells_src, pos).r;
+}
+
+// Neighbor offsets in clockwise order starting from north, matching the bits of a neighborhood mask
+const NEIGHBORS = array<vec2<i32>, 8>(
+    vec2<i32>(0, -1),
+    vec2<i32>(1, -1),
+    vec2<i32>(1, 0),
+    vec2<i32>(1, 1),
+    vec2<i32>(0, 1),
+    vec2<i32>(-1, 1),
+    vec2<i32>(-1, 0),
+    vec2<i32>(-1, -1),
+);
+
+// Cells hold their state number: 0 is dead, 1 is alive, anything above is dying
+fn state(pos: vec2<i32>) -> u32 {
+    return u32(cell(pos) + 0.5);
+}
+
+fn in_table(table: array<vec4<u32>, 2>, neighborhood: u32) -> bool {
+    // Copied since arrays passed by value can only be indexed by constants
+    var t = table;
+    let word = t[neighborhood / 128u][(neighborhood / 32u) % 4u];
+    return ((word >> (neighborhood % 32u)) & 1u) == 1u;
+}
+
+fn next_state(state: u32, neighborhood: u32) -> u32 {
+    if state == 0u {
+        return u32(in_table(params.rule.birth, neighborhood));
+    }
+    if state == 1u && in_table(params.rule.survival, neighborhood) {
+        return 1u;
+    }
+    // Failing to survive starts dying, and the last dying state dies
+    return (state + 1u) % params.rule.states;
+}
+
+@compute @workgroup_size(8, 8)
+fn cs_main(
+    @builtin(global_invocation_id) id: vec3<u32>,
+) {
+    if id.x >= params.width || id.y >= params.height {
+        return;
+    }
+    let pos = vec2<i32>(id.xy);
+
+    var neighbors = NEIGHBORS;
+    var neighborhood = 0u;
+    for (var i = 0; i < 8; i++) {
+        neighborhood |= u32(state(pos + neighbors[i]) == 1u) << u32(i);
+    }
+
+    let next = next_state(state(pos), neighborhood);
+    textureStore(cells_dst, pos, vec4<f32>(f32(next), 0.0, 0.0, 1.0));
+}
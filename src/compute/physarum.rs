@@ -0,0 +1,298 @@
+use super::{compute_pass::ComputePass, gpu_simulation::GpuSimulation};
+use crate::shared::{
+    rng::Rng,
+    shader::{self, Shader},
+    sim_params::SimulationParamsBuf,
+    texture::Texture,
+};
+use anyhow::{bail, Result};
+use std::f32::consts::TAU;
+use wgpu::util::DeviceExt;
+
+const AGENT_WORKGROUP_SIZE: u32 = 64;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Agent {
+    position: [f32; 2],
+    heading: f32,
+    _padding: f32,
+}
+
+/// Agents sense, turn, move and deposit into the trail,
+/// then the trail is diffused, decayed and combined with the deposits.
+///
+/// The agents and the fixed point trail they deposit each step are kept in the returned buffers,
+/// beyond the trail map held in the ping-pong textures
+pub fn new(
+    device: &wgpu::Device,
+    textures: &[Texture; 2],
+    sim_params: &SimulationParamsBuf,
+    seed: u64,
+) -> Result<GpuSimulation> {
+    let params = &sim_params.params;
+    let limits = device.limits();
+    let max_binding_size = u64::from(limits.max_storage_buffer_binding_size);
+    // Agents are dispatched along x only, in workgroups of AGENT_WORKGROUP_SIZE
+    let max_agents = (max_binding_size / std::mem::size_of::<Agent>() as u64).min(
+        u64::from(limits.max_compute_workgroups_per_dimension) * u64::from(AGENT_WORKGROUP_SIZE),
+    );
+    if params.physarum.agent_count == 0 || u64::from(params.physarum.agent_count) > max_agents {
+        bail!(
+            "Physarum needs 1 to {max_agents} agents on this adapter, found {}",
+            params.physarum.agent_count
+        );
+    }
+    let deposits_size =
+        u64::from(params.width) * u64::from(params.height) * std::mem::size_of::<u32>() as u64;
+    if deposits_size > max_binding_size {
+        bail!(
+            "Physarum deposits of a {}x{} grid need {deposits_size} bytes, \
+            more than the adapter's storage binding limit of {max_binding_size}",
+            params.width,
+            params.height
+        );
+    }
+    let shader = Shader::new(
+        "physarum.wgsl",
+        &[
+            shader::SIM_PARAMS,
+            shader::BOUNDARY,
+            include_str!("physarum.wgsl"),
+        ],
+        device,
+    );
+
+    let agents_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("Physarum Agents Buffer"),
+        contents: bytemuck::cast_slice(&initial_agents(
+            params.width,
+            params.height,
+            params.physarum.agent_count,
+            seed,
+        )),
+        usage: wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::COPY_DST
+            | wgpu::BufferUsages::COPY_SRC,
+    });
+    let deposits_buf = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Physarum Deposits Buffer"),
+        size: deposits_size,
+        usage: wgpu::BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    });
+
+    let storage = wgpu::BindingType::Buffer {
+        ty: wgpu::BufferBindingType::Storage { read_only: false },
+        has_dynamic_offset: false,
+        min_binding_size: None,
+    };
+    let extra_bindings = [
+        (storage, agents_buf.as_entire_binding()),
+        (storage, deposits_buf.as_entire_binding()),
+    ];
+    let passes = vec![
+        ComputePass::new(
+            device,
+            "Physarum Agents",
+            &shader,
+            "agents_main",
+            textures,
+            sim_params,
+            &extra_bindings,
+            (
+                params.physarum.agent_count.div_ceil(AGENT_WORKGROUP_SIZE),
+                1,
+                1,
+            ),
+        ),
+        ComputePass::new(
+            device,
+            "Physarum Diffuse",
+            &shader,
+            "diffuse_main",
+            textures,
+            sim_params,
+            &extra_bindings,
+            ComputePass::texel_workgroups(textures),
+        ),
+    ];
+
+    Ok(GpuSimulation {
+        passes,
+        state_buffers: vec![agents_buf],
+        _buffers: vec![deposits_buf],
+    })
+}
+
+/// Agents spread uniformly over the grid with random headings
+fn initial_agents(width: u32, height: u32, count: u32, seed: u64) -> Vec<Agent> {
+    let mut rng = Rng::new(seed);
+    (0..count)
+        .map(|_| Agent {
+            position: [
+                rng.next_f32() * width as f32,
+                rng.next_f32() * height as f32,
+            ],
+            heading: rng.next_f32() * TAU,
+            _padding: 0.0,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compute::simulation::{tests::compute_gpu, ComputeBackend, Simulation},
+        shared::{
+            boundary::{
+                tests::{params_of, HEIGHT, WIDTH},
+                Boundary,
+            },
+            gpu::Gpu,
+            sim_params::{SimulationMode, SimulationParams},
+        },
+    };
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    fn error(gpu: &Gpu, width: u32, height: u32, agent_count: u32) -> String {
+        let size = winit::dpi::PhysicalSize::new(width, height);
+        let mut params = SimulationParams::new(&size, SimulationMode::Physarum);
+        params.physarum.agent_count = agent_count;
+        let sim_params = SimulationParamsBuf::new(&gpu.device, params);
+        // Limits are checked before the textures are used, so they needn't match the grid
+        let texture_size = winit::dpi::PhysicalSize::new(8, 8);
+        let textures =
+            [0, 1].map(|_| Texture::new(&gpu.device, &texture_size, wgpu::TextureFormat::R32Float));
+        match new(&gpu.device, &textures, &sim_params, 0) {
+            Ok(_) => String::new(),
+            Err(error) => format!("{error:#}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_buffers_and_dispatches_beyond_limits() {
+        let Some(gpu) = compute_gpu().await else {
+            return;
+        };
+        let limits = gpu.device.limits();
+        let max_binding_size = u64::from(limits.max_storage_buffer_binding_size);
+        let max_agents = (max_binding_size / std::mem::size_of::<Agent>() as u64).min(
+            u64::from(limits.max_compute_workgroups_per_dimension)
+                * u64::from(AGENT_WORKGROUP_SIZE),
+        );
+        assert_eq!(error(&gpu, 8, 8, 1), "");
+        for agent_count in [0, max_agents as u32 + 1] {
+            assert_eq!(
+                error(&gpu, 8, 8, agent_count),
+                format!(
+                    "Physarum needs 1 to {max_agents} agents on this adapter, found {agent_count}"
+                )
+            );
+        }
+
+        // A square grid just too large for the deposits to bind
+        let side = ((max_binding_size / 4) as f64).sqrt() as u32 + 1;
+        assert_eq!(
+            error(&gpu, side, side, 1),
+            format!(
+                "Physarum deposits of a {side}x{side} grid need {} bytes, \
+                more than the adapter's storage binding limit of {max_binding_size}",
+                u64::from(side) * u64::from(side) * 4
+            )
+        );
+    }
+
+    /// Agents heading out of the grid past the left, top, right and bottom edges, moved once
+    /// on a grid without trail so they don't turn, then compared by position and heading
+    #[tokio::test]
+    async fn agents_cross_edges() {
+        let Some(gpu) = compute_gpu().await else {
+            return;
+        };
+        let (w, h) = (WIDTH as f32, HEIGHT as f32);
+        let agents = [
+            [0.5, 3.25, PI],
+            [2.25, 0.5, -FRAC_PI_2],
+            [w - 0.5, h - 2.75, 0.0],
+            [w - 3.75, h - 0.5, FRAC_PI_2],
+        ];
+        let bounced = [
+            [0.5, 3.25, 0.0],
+            [2.25, 0.5, FRAC_PI_2],
+            [w - 0.5, h - 2.75, PI],
+            [w - 3.75, h - 0.5, -FRAC_PI_2],
+        ];
+        for (boundary, expected) in [
+            (
+                Boundary::Torus,
+                [
+                    [w - 0.5, 3.25, PI],
+                    [2.25, h - 0.5, -FRAC_PI_2],
+                    [0.5, h - 2.75, 0.0],
+                    [w - 3.75, 0.5, FRAC_PI_2],
+                ],
+            ),
+            (Boundary::Dead, bounced),
+            (Boundary::Mirror, bounced),
+            // Crossing top or bottom reflects x, and the heading along it
+            (
+                Boundary::KleinBottle,
+                [
+                    [w - 0.5, 3.25, PI],
+                    [w - 2.25, h - 0.5, -FRAC_PI_2],
+                    [0.5, h - 2.75, 0.0],
+                    [3.75, 0.5, FRAC_PI_2],
+                ],
+            ),
+            // Crossing left or right also reflects y
+            (
+                Boundary::CrossSurface,
+                [
+                    [w - 0.5, h - 3.25, PI],
+                    [w - 2.25, h - 0.5, -FRAC_PI_2],
+                    [0.5, 2.75, 0.0],
+                    [3.75, 0.5, FRAC_PI_2],
+                ],
+            ),
+        ] {
+            let mut params = params_of(SimulationMode::Physarum, boundary, WIDTH, HEIGHT);
+            params.physarum.agent_count = agents.len() as u32;
+            let sim_params = SimulationParamsBuf::new(&gpu.device, params);
+            let mut simulation = Simulation::new(
+                &gpu.device,
+                &gpu.queue,
+                &gpu.adapter,
+                &sim_params,
+                0,
+                ComputeBackend::Gpu,
+            )
+            .unwrap();
+            // Agents are a position, a heading and padding
+            let agent_bytes: Vec<u8> = agents
+                .iter()
+                .flat_map(|&[x, y, heading]| [x, y, heading, 0.0])
+                .flat_map(f32::to_ne_bytes)
+                .collect();
+            let trail = vec![0.0; (WIDTH * HEIGHT) as usize];
+            simulation
+                .restore(&gpu.queue, 0, &[trail.clone(), trail], &[agent_bytes])
+                .unwrap();
+            let step = simulation.step(&gpu.device, &gpu.queue).finish();
+            gpu.queue.submit(Some(step));
+
+            let moved = simulation.read_state_buffers(&gpu.device, &gpu.queue);
+            let moved: &[f32] = bytemuck::cast_slice(&moved[0]);
+            for (index, (agent, expected)) in moved.chunks(4).zip(expected).enumerate() {
+                let turned = (agent[2] - expected[2]).rem_euclid(TAU);
+                assert!(
+                    (agent[0] - expected[0]).abs() < 1e-3
+                        && (agent[1] - expected[1]).abs() < 1e-3
+                        && turned.min(TAU - turned) < 1e-3,
+                    "{boundary:?} agent {index}: {agent:?}, expected {expected:?}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,127 @@
+struct Agent {
+    position: vec2<f32>,
+    heading: f32,
+}
+
+@group(0) @binding(0)
+var trail_src: texture_2d<f32>;
+@group(0) @binding(1)
+var trail_dst: texture_storage_2d<r32float, write>;
+@group(0) @binding(2)
+var<uniform> params: SimulationParams;
+@group(0) @binding(3)
+var<storage, read_write> agents: array<Agent>;
+@group(0) @binding(4)
+var<storage, read_write> deposits: array<atomic<u32>>;
+
+// Deposits are accumulated atomically as fixed point
+const DEPOSIT_SCALE = 65536.0;
+
+const PI = 3.14159265;
+
+fn trail(pos: vec2<f32>) -> f32 {
+    return load_cell(trail_src, vec2<i32>(floor(pos))).r;
+}
+
+// Brings an agent that moved past an edge back onto the grid, following the boundary.
+// Fixed edges and mirrors bounce it back, twisted edges reflect the axis along them
+fn cross_boundary(agent: Agent) -> Agent {
+    var out = agent;
+    let size = vec2<f32>(f32(params.width), f32(params.height));
+    let wraps = floor(agent.position / size);
+    let boundary = params.boundary;
+
+    if boundary == BOUNDARY_TORUS || boundary == BOUNDARY_KLEIN_BOTTLE
+        || boundary == BOUNDARY_CROSS_SURFACE {
+        out.position = agent.position - wraps * size;
+        let twist_x = boundary != BOUNDARY_TORUS && wraps.y != 0.0;
+        let twist_y = boundary == BOUNDARY_CROSS_SURFACE && wraps.x != 0.0;
+        if twist_x {
+            out.position.x = size.x - out.position.x;
+            out.heading = PI - out.heading;
+        }
+        if twist_y {
+            out.position.y = size.y - out.position.y;
+            out.heading = -out.heading;
+        }
+    } else {
+        if wraps.x != 0.0 {
+            out.position.x = select(-agent.position.x, 2.0 * size.x - agent.position.x, wraps.x > 0.0);
+            out.heading = PI - out.heading;
+        }
+        if wraps.y != 0.0 {
+            out.position.y = select(-agent.position.y, 2.0 * size.y - agent.position.y, wraps.y > 0.0);
+            out.heading = -out.heading;
+        }
+    }
+    // Reflections can land exactly on the far edge
+    out.position = clamp(out.position, vec2<f32>(0.0), size - 0.001);
+    return out;
+}
+
+fn direction(angle: f32) -> vec2<f32> {
+    return vec2<f32>(cos(angle), sin(angle));
+}
+
+// PCG hash, for random turns without storing generator state per agent
+fn hash(input: u32) -> u32 {
+    let state = input * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+@compute @workgroup_size(64)
+fn agents_main(
+    @builtin(global_invocation_id) id: vec3<u32>,
+) {
+    if id.x >= params.physarum.agent_count {
+        return;
+    }
+    var agent = agents[id.x];
+    let p = params.physarum;
+
+    let forward = trail(agent.position + direction(agent.heading) * p.sensor_distance);
+    let left = trail(agent.position + direction(agent.heading - p.sensor_angle) * p.sensor_distance);
+    let right = trail(agent.position + direction(agent.heading + p.sensor_angle) * p.sensor_distance);
+
+    if forward > left && forward > right {
+        // Keep going straight
+    } else if forward < left && forward < right {
+        let random = hash(id.x ^ hash(bitcast<u32>(agent.position.x) ^ hash(bitcast<u32>(agent.position.y))));
+        agent.heading += select(-p.turn_speed, p.turn_speed, (random & 1u) == 1u);
+    } else if left > right {
+        agent.heading -= p.turn_speed;
+    } else if right > left {
+        agent.heading += p.turn_speed;
+    }
+
+    agent.position += direction(agent.heading) * p.move_speed;
+    agent = cross_boundary(agent);
+    agents[id.x] = agent;
+
+    let cell = vec2<u32>(agent.position);
+    atomicAdd(&deposits[cell.y * params.width + cell.x], u32(p.deposit * DEPOSIT_SCALE));
+}
+
+@compute @workgroup_size(8, 8)
+fn diffuse_main(
+    @builtin(global_invocation_id) id: vec3<u32>,
+) {
+    if id.x >= params.width || id.y >= params.height {
+        return;
+    }
+    let pos = vec2<i32>(id.xy);
+
+    var sum = 0.0;
+    for (var dy = -1; dy <= 1; dy++) {
+        for (var dx = -1; dx <= 1; dx++) {
+            sum += load_cell(trail_src, pos + vec2<i32>(dx, dy)).r;
+        }
+    }
+    let center = textureLoad(trail_src, id.xy, 0).r;
+    let diffused = mix(center, sum / 9.0, params.physarum.diffuse);
+
+    let deposit = f32(atomicExchange(&deposits[id.y * params.width + id.x], 0u)) / DEPOSIT_SCALE;
+    let next = diffused * (1.0 - params.physarum.decay) + deposit;
+    textureStore(trail_dst, pos, vec4<f32>(next, 0.0, 0.0, 1.0));
+}
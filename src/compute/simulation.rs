@@ -1,9 +1,417 @@
-pub struct _Simulation {
-    generation: usize,
+use super::{
+    cpu_simulation::CpuSimulation, gpu_simulation::GpuSimulation, gray_scott, larger_than_life,
+    lenia, life, physarum, smooth_life,
+};
+use crate::shared::{
+    sim_params::{SimulationMode, SimulationParams, SimulationParamsBuf},
+    texture::Texture,
+};
+use anyhow::{bail, Result};
+use std::str::FromStr;
+
+pub struct Simulation {
+    pub generation: usize,
+    /// Seed the random initial state was generated from
+    pub seed: u64,
+    /// Parameters the simulation was built with
+    params: SimulationParams,
+    // Two textures to alternate reading the previous generation and writing the next
+    textures: [Texture; 2],
+    backend: Backend,
+}
+
+/// Where to step the simulation, `Auto` preferring the GPU when it can run compute shaders.
+/// Either way the generations live in the textures of an adapter
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ComputeBackend {
+    #[default]
+    Auto,
+    Gpu,
+    Cpu,
+}
+
+enum Backend {
+    Gpu(GpuSimulation),
+    // For adapters without compute shader support:
+    // generations are stepped on the CPU and uploaded into the textures for rendering
+    Cpu(CpuSimulation),
+}
+
+impl Simulation {
+    /// Random initial states are generated from `seed`
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        adapter: &wgpu::Adapter,
+        sim_params: &SimulationParamsBuf,
+        seed: u64,
+        compute_backend: ComputeBackend,
+    ) -> Result<Self> {
+        let params = &sim_params.params;
+        let max_size = device.limits().max_texture_dimension_2d;
+        if params.width > max_size || params.height > max_size {
+            bail!(
+                "Grid of {}x{} cells exceeds the adapter's texture size limit of {max_size}",
+                params.width,
+                params.height
+            );
+        }
+        let size = &winit::dpi::PhysicalSize::new(params.width, params.height);
+        let format = params.mode().texture_format(adapter);
+        let textures = [
+            Texture::new(device, size, format),
+            Texture::new(device, size, format),
+        ];
+
+        // Downlevel adapters can render but not necessarily run compute shaders
+        let compute_supported = adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS);
+        let on_gpu = match compute_backend {
+            ComputeBackend::Auto => {
+                if !compute_supported {
+                    log::warn!("Adapter does not support compute shaders, simulating on the CPU");
+                }
+                compute_supported
+            }
+            ComputeBackend::Gpu if !compute_supported => {
+                bail!("Adapter does not support compute shaders")
+            }
+            ComputeBackend::Gpu => true,
+            ComputeBackend::Cpu => false,
+        };
+        let backend = match (params.mode(), on_gpu) {
+            (SimulationMode::Life, true) => Backend::Gpu(life::new(device, &textures, sim_params)?),
+            (SimulationMode::LargerThanLife, true) => {
+                Backend::Gpu(larger_than_life::new(device, &textures, sim_params)?)
+            }
+            (SimulationMode::Life | SimulationMode::LargerThanLife, false) => {
+                Backend::Cpu(CpuSimulation::new(params))
+            }
+            (SimulationMode::Physarum, true) => {
+                Backend::Gpu(physarum::new(device, &textures, sim_params, seed)?)
+            }
+            (SimulationMode::GrayScott, true) => {
+                Backend::Gpu(gray_scott::new(device, queue, &textures, sim_params, seed)?)
+            }
+            (SimulationMode::Lenia, true) => {
+                Backend::Gpu(lenia::new(device, &textures, sim_params)?)
+            }
+            (SimulationMode::SmoothLife, true) => Backend::Gpu(smooth_life::new(
+                device, queue, &textures, sim_params, seed,
+            )?),
+            (mode, false) => bail!("{mode:?} mode can only be simulated with compute shaders"),
+        };
+
+        Ok(Self {
+            generation: 0,
+            seed,
+            params: *params,
+            textures,
+            backend,
+        })
+    }
+
+    pub fn mode(&self) -> SimulationMode {
+        self.params.mode()
+    }
+
+    pub fn params(&self) -> &SimulationParams {
+        &self.params
+    }
+
+    /// Both ping-pong textures, in the order indexed by [`Simulation::current`]
+    pub fn textures(&self) -> &[Texture; 2] {
+        &self.textures
+    }
+
+    /// Index of the texture holding the latest generation
+    pub fn current(&self) -> usize {
+        self.generation % 2
+    }
+
+    /// Encodes one generation, reading the current texture and writing the other one
+    pub fn step(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> wgpu::CommandEncoder {
+        let mut command_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Simulation Encoder"),
+        });
+        let current = self.current();
+        match &mut self.backend {
+            Backend::Gpu(gpu_simulation) => gpu_simulation.step(&mut command_encoder, current),
+            Backend::Cpu(cpu_simulation) => {
+                cpu_simulation.step();
+                self.textures[1 - current].write(queue, cpu_simulation.cells());
+            }
+        }
+
+        self.generation += 1;
+        command_encoder
+    }
+
+    /// Replaces the latest generation with the given cells, in row-major order
+    pub fn write_cells(&mut self, queue: &wgpu::Queue, cells: &[f32]) {
+        if let Backend::Cpu(cpu_simulation) = &mut self.backend {
+            cpu_simulation.set_cells(cells);
+        }
+        self.textures[self.current()].write(queue, cells);
+    }
+
+    /// Sets or clears the cells of the latest generation covered by rows of `(y, first x, last x)`.
+    /// Parts beyond the edges of the grid are left out
+    pub fn paint(
+        &mut self,
+        queue: &wgpu::Queue,
+        spans: impl IntoIterator<Item = (i32, i32, i32)>,
+        alive: bool,
+    ) {
+        let texture = &self.textures[self.current()];
+        let size = texture.texture.size();
+        let mut texel = vec![0.0; texture.channels()];
+        for (channel, value) in texel.iter_mut().zip(self.mode().painted_cell(alive)) {
+            *channel = value;
+        }
+
+        for (y, first, last) in spans {
+            let first = first.max(0);
+            let last = last.min(size.width as i32 - 1);
+            if y < 0 || y >= size.height as i32 || first > last {
+                continue;
+            }
+            let row = texel.repeat((last - first + 1) as usize);
+            texture.write_row(queue, [first as u32, y as u32], &row);
+            if let Backend::Cpu(cpu_simulation) = &mut self.backend {
+                cpu_simulation.set_span(y as usize * size.width as usize + first as usize, &row);
+            }
+        }
+    }
+
+    /// Replaces the cells of the latest generation under a rectangle of numbered states,
+    /// `width` states wide in row-major order, with its top left cell at `origin`.
+    /// Parts beyond the edges of the grid are left out
+    pub fn stamp(&mut self, queue: &wgpu::Queue, origin: [i32; 2], width: u32, states: &[u8]) {
+        let cells: Vec<[f32; 2]> = states
+            .iter()
+            .map(|&state| self.mode().pattern_cell(state))
+            .collect();
+        self.stamp_cells(queue, origin, width, &cells);
+    }
+
+    /// Replaces the cells of the latest generation under a rectangle of values from 0 for
+    /// cleared to 1 for painted alive, like [`Simulation::stamp`]
+    pub fn stamp_values(
+        &mut self,
+        queue: &wgpu::Queue,
+        origin: [i32; 2],
+        width: u32,
+        values: &[f32],
+    ) {
+        let cells: Vec<[f32; 2]> = values
+            .iter()
+            .map(|&value| self.mode().blended_cell(value))
+            .collect();
+        self.stamp_cells(queue, origin, width, &cells);
+    }
+
+    /// Replaces the cells under a rectangle of leading channels, clipped to the grid
+    fn stamp_cells(
+        &mut self,
+        queue: &wgpu::Queue,
+        origin: [i32; 2],
+        width: u32,
+        cells: &[[f32; 2]],
+    ) {
+        let texture = &self.textures[self.current()];
+        let size = texture.texture.size();
+        let channels = texture.channels();
+        let first = origin[0].max(0);
+        let end = (origin[0] + width as i32).min(size.width as i32);
+        if first >= end {
+            return;
+        }
+
+        for (row, row_cells) in cells.chunks(width as usize).enumerate() {
+            let y = origin[1] + row as i32;
+            if y < 0 || y >= size.height as i32 {
+                continue;
+            }
+            let row_cells = &row_cells[(first - origin[0]) as usize..(end - origin[0]) as usize];
+            let mut texels = Vec::with_capacity(row_cells.len() * channels);
+            for cell in row_cells {
+                texels.extend(cell.iter().chain(std::iter::repeat(&0.0)).take(channels));
+            }
+            texture.write_row(queue, [first as u32, y as u32], &texels);
+            if let Backend::Cpu(cpu_simulation) = &mut self.backend {
+                cpu_simulation.set_span(y as usize * size.width as usize + first as usize, &texels);
+            }
+        }
+    }
+
+    /// Reads the mode-specific state kept outside the textures back from the GPU, like Physarum
+    /// agents. Random numbers are drawn from generators seeded anew from `seed` or from hashes
+    /// of this state, so with the textures it is the whole state of the simulation
+    pub fn read_state_buffers(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Vec<Vec<u8>> {
+        match &self.backend {
+            Backend::Gpu(gpu_simulation) => gpu_simulation.read_state(device, queue),
+            Backend::Cpu(_) => Vec::new(),
+        }
+    }
+
+    /// Continues from a saved generation: both ping-pong textures, tightly packed in the order
+    /// indexed by [`Simulation::current`], and the buffers of [`Simulation::read_state_buffers`]
+    pub fn restore(
+        &mut self,
+        queue: &wgpu::Queue,
+        generation: usize,
+        textures: &[Vec<f32>; 2],
+        state_buffers: &[Vec<u8>],
+    ) -> Result<()> {
+        let size = self.textures[0].texture.size();
+        let expected = size.width as usize * size.height as usize * self.textures[0].channels();
+        for (index, cells) in textures.iter().enumerate() {
+            if cells.len() != expected {
+                bail!(
+                    "Expected {expected} values in texture {index}, found {}",
+                    cells.len()
+                );
+            }
+        }
+        match &mut self.backend {
+            Backend::Gpu(gpu_simulation) => gpu_simulation.write_state(queue, state_buffers)?,
+            Backend::Cpu(cpu_simulation) => {
+                if !state_buffers.is_empty() {
+                    bail!("Expected no state buffers, found {}", state_buffers.len());
+                }
+                cpu_simulation.generation = generation;
+                cpu_simulation.set_cells(&textures[generation % 2]);
+            }
+        }
+        self.generation = generation;
+        for (texture, cells) in self.textures.iter().zip(textures) {
+            texture.write(queue, cells);
+        }
+        Ok(())
+    }
+
+    /// Reads the latest generation back from the GPU, in row-major order
+    pub fn read_cells(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<Vec<f32>> {
+        match &self.backend {
+            Backend::Gpu(_) => self.textures[self.current()].read(device, queue),
+            Backend::Cpu(cpu_simulation) => Ok(cpu_simulation.cells().to_vec()),
+        }
+    }
 }
 
-impl _Simulation {
-    pub fn _new() -> Self {
-        todo!()
+impl FromStr for ComputeBackend {
+    type Err = anyhow::Error;
+
+    fn from_str(backend: &str) -> Result<Self> {
+        Ok(match backend.to_ascii_lowercase().as_str() {
+            "auto" => Self::Auto,
+            "gpu" => Self::Gpu,
+            "cpu" => Self::Cpu,
+            _ => bail!("Unknown compute backend '{backend}', expected auto, gpu or cpu"),
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::shared::gpu::Gpu;
+
+    /// Device of any adapter that runs compute shaders, software ones included,
+    /// or `None` so GPU tests pass trivially on machines without one
+    pub async fn compute_gpu() -> Option<Gpu> {
+        let gpu = match Gpu::new(Gpu::instance(wgpu::Backends::all()), None).await {
+            Ok(gpu) => gpu,
+            Err(error) => {
+                eprintln!("Skipping GPU test: {error}");
+                return None;
+            }
+        };
+        let compute_supported = gpu
+            .adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS);
+        if !compute_supported {
+            eprintln!("Skipping GPU test, the adapter does not support compute shaders");
+            return None;
+        }
+        Some(gpu)
+    }
+
+    /// Steps `cells` under `params` for `steps` generations and reads the result back
+    pub fn run(
+        gpu: &Gpu,
+        params: SimulationParams,
+        cells: &[f32],
+        steps: usize,
+        backend: ComputeBackend,
+    ) -> Vec<f32> {
+        let sim_params = SimulationParamsBuf::new(&gpu.device, params);
+        let mut simulation = Simulation::new(
+            &gpu.device,
+            &gpu.queue,
+            &gpu.adapter,
+            &sim_params,
+            0,
+            backend,
+        )
+        .unwrap();
+        simulation.write_cells(&gpu.queue, cells);
+        let command_buffers: Vec<_> = (0..steps)
+            .map(|_| simulation.step(&gpu.device, &gpu.queue).finish())
+            .collect();
+        gpu.queue.submit(command_buffers);
+        simulation.read_cells(&gpu.device, &gpu.queue).unwrap()
+    }
+
+    /// Row-major grid with the given cells alive
+    pub fn grid(width: usize, height: usize, alive: &[(usize, usize)]) -> Vec<f32> {
+        let mut cells = vec![0.0; width * height];
+        for &(x, y) in alive {
+            cells[y * width + x] = 1.0;
+        }
+        cells
+    }
+
+    #[tokio::test]
+    async fn blinker_oscillates_on_gpu() {
+        let Some(gpu) = compute_gpu().await else {
+            return;
+        };
+        let params =
+            SimulationParams::new(&winit::dpi::PhysicalSize::new(8, 8), SimulationMode::Life);
+        let horizontal = grid(8, 8, &[(2, 3), (3, 3), (4, 3)]);
+        let vertical = grid(8, 8, &[(3, 2), (3, 3), (3, 4)]);
+        assert_eq!(
+            run(&gpu, params, &horizontal, 1, ComputeBackend::Gpu),
+            vertical
+        );
+        assert_eq!(
+            run(&gpu, params, &horizontal, 2, ComputeBackend::Gpu),
+            horizontal
+        );
+        assert_eq!(
+            run(&gpu, params, &horizontal, 7, ComputeBackend::Gpu),
+            vertical
+        );
+    }
+
+    #[tokio::test]
+    async fn glider_moves_on_gpu() {
+        let Some(gpu) = compute_gpu().await else {
+            return;
+        };
+        let params =
+            SimulationParams::new(&winit::dpi::PhysicalSize::new(16, 16), SimulationMode::Life);
+        let glider = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+        let moved = glider.map(|(x, y)| (x + 1, y + 1));
+        assert_eq!(
+            run(&gpu, params, &grid(16, 16, &glider), 4, ComputeBackend::Gpu),
+            grid(16, 16, &moved)
+        );
     }
 }
@@ -0,0 +1,167 @@
+use super::{compute_pass::ComputePass, gpu_simulation::GpuSimulation};
+use crate::shared::{
+    rng::Rng,
+    shader::{self, Shader},
+    sim_params::{SimulationParamsBuf, SmoothLifeParams},
+    texture::Texture,
+};
+use anyhow::{bail, Result};
+use wgpu::util::DeviceExt;
+
+/// Integrates the inner disk and outer annulus around every cell and applies the transition,
+/// starting from random squares the size of the outer radius
+pub fn new(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    textures: &[Texture; 2],
+    sim_params: &SimulationParamsBuf,
+    seed: u64,
+) -> Result<GpuSimulation> {
+    let params = &sim_params.params;
+    let smooth_life = &params.smooth_life;
+    smooth_life.check()?;
+
+    let shader = Shader::new(
+        "smooth_life.wgsl",
+        &[
+            shader::SIM_PARAMS,
+            shader::BOUNDARY,
+            include_str!("smooth_life.wgsl"),
+        ],
+        device,
+    );
+
+    let side = 2 * smooth_life.outer_radius.ceil() as u64 + 1;
+    let kernel_size = side * side * std::mem::size_of::<[f32; 2]>() as u64;
+    let max_binding_size = u64::from(device.limits().max_storage_buffer_binding_size);
+    if kernel_size > max_binding_size {
+        bail!(
+            "SmoothLife kernel of outer radius {} needs {kernel_size} bytes, \
+            more than the adapter's storage binding limit of {max_binding_size}",
+            smooth_life.outer_radius
+        );
+    }
+    let kernel_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("SmoothLife Kernel Buffer"),
+        contents: bytemuck::cast_slice(&kernel(smooth_life)),
+        usage: wgpu::BufferUsages::STORAGE,
+    });
+
+    textures[0].write(
+        queue,
+        &initial_state(params.width, params.height, smooth_life.outer_radius, seed),
+    );
+
+    let pass = ComputePass::new(
+        device,
+        "SmoothLife",
+        &shader,
+        "cs_main",
+        textures,
+        sim_params,
+        &[(
+            wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            kernel_buf.as_entire_binding(),
+        )],
+        ComputePass::texel_workgroups(textures),
+    );
+
+    Ok(GpuSimulation {
+        passes: vec![pass],
+        state_buffers: Vec::new(),
+        _buffers: vec![kernel_buf],
+    })
+}
+
+/// Inner disk and outer annulus weights over the square of side `2 * ceil(outer_radius) + 1`,
+/// in row-major order and each summing to one so the integrals are fillings.
+/// Cells straddling a boundary are weighted by how far inside it they are, for smooth edges
+fn kernel(params: &SmoothLifeParams) -> Vec<[f32; 2]> {
+    let radius = params.outer_radius.ceil() as i64;
+    let mut weights: Vec<[f32; 2]> = (-radius..=radius)
+        .flat_map(|dy| (-radius..=radius).map(move |dx| (dx, dy)))
+        .map(|(dx, dy)| {
+            let distance = ((dx * dx + dy * dy) as f32).sqrt();
+            let inner = (params.inner_radius + 0.5 - distance).clamp(0.0, 1.0);
+            let outer = (params.outer_radius + 0.5 - distance).clamp(0.0, 1.0) - inner;
+            [inner, outer]
+        })
+        .collect();
+
+    let inner_total: f32 = weights.iter().map(|[inner, _]| inner).sum();
+    let outer_total: f32 = weights.iter().map(|[_, outer]| outer).sum();
+    for [inner, outer] in &mut weights {
+        *inner /= inner_total;
+        *outer /= outer_total;
+    }
+    weights
+}
+
+/// Rafler's initial condition: alive squares as wide as the outer radius scattered at random,
+/// covering about half the grid before overlaps.
+/// The side is at most [`SmoothLifeParams::MAX_RADIUS`], so its area can't overflow
+fn initial_state(width: u32, height: u32, outer_radius: f32, seed: u64) -> Vec<f32> {
+    let mut rng = Rng::new(seed);
+    let (width, height) = (width as usize, height as usize);
+    let side = outer_radius.round() as usize;
+    let mut cells = vec![0.0; width * height];
+    for _ in 0..(width * height) / (2 * side * side) {
+        let left = (rng.next_f32() * width as f32) as usize;
+        let top = (rng.next_f32() * height as f32) as usize;
+        for y in top..top + side {
+            for x in left..left + side {
+                cells[(y % height) * width + x % width] = 1.0;
+            }
+        }
+    }
+    cells
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compute::simulation::tests::compute_gpu,
+        shared::{boundary::tests::assert_reads_across_edges, sim_params::SimulationMode},
+    };
+
+    #[test]
+    fn kernels_sum_to_one_up_to_the_largest_radius() {
+        for outer_radius in [1.0, 21.0, SmoothLifeParams::MAX_RADIUS] {
+            let mut params = SmoothLifeParams::default();
+            params.inner_radius = outer_radius / 3.0;
+            params.outer_radius = outer_radius;
+            params.check().unwrap();
+            let weights = kernel(&params);
+            let side = 2 * outer_radius.ceil() as usize + 1;
+            assert_eq!(weights.len(), side * side);
+            for ring in 0..2 {
+                let total: f64 = weights.iter().map(|weight| f64::from(weight[ring])).sum();
+                assert!(
+                    (total - 1.0).abs() < 1e-3,
+                    "ring {ring} of outer radius {outer_radius} sums to {total}"
+                );
+            }
+        }
+        let cells = initial_state(64, 48, SmoothLifeParams::MAX_RADIUS, 0);
+        assert_eq!(cells.len(), 64 * 48);
+    }
+
+    #[tokio::test]
+    async fn reads_across_twisted_and_mirrored_edges() {
+        let Some(gpu) = compute_gpu().await else {
+            return;
+        };
+        assert_reads_across_edges(&gpu, SimulationMode::SmoothLife, |params| {
+            // Disks reaching across the edge but well within the grid
+            params.smooth_life.inner_radius = 1.5;
+            params.smooth_life.outer_radius = 4.0;
+            // Cells barely reached by the blob across an edge are born
+            params.smooth_life.birth = [0.01, 0.9];
+        });
+    }
+}
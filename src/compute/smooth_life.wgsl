@@ -0,0 +1,69 @@
+@group(0) @binding(0)
+var cells_src: texture_2d<f32>;
+@group(0) @binding(1)
+var cells_dst: texture_storage_2d<r32float, write>;
+@group(0) @binding(2)
+var<uniform> params: SimulationParams;
+// Inner disk and outer annulus weights over a square around the cell, precomputed on the CPU
+@group(0) @binding(3)
+var<storage, read> kernel: array<vec2<f32>>;
+
+fn cell(pos: vec2<i32>) -> f32 {
+    return load_cell(cells_src, pos).r;
+}
+
+// Smooth step from 0 to 1 around a, about alpha wide
+fn sigmoid(x: f32, a: f32, alpha: f32) -> f32 {
+    return 1.0 / (1.0 + exp(-(x - a) * 4.0 / alpha));
+}
+
+// Smooth indicator of the interval [a, b]
+fn sigmoid_interval(x: f32, a: f32, b: f32) -> f32 {
+    let alpha = params.smooth_life.alpha_n;
+    return sigmoid(x, a, alpha) * (1.0 - sigmoid(x, b, alpha));
+}
+
+// Smooth blend from x for dead cells to y for alive ones
+fn sigmoid_mix(x: f32, y: f32, m: f32) -> f32 {
+    return mix(x, y, sigmoid(m, 0.5, params.smooth_life.alpha_m));
+}
+
+// Transition function of the annulus filling n and disk filling m
+fn transition(n: f32, m: f32) -> f32 {
+    let p = params.smooth_life;
+    return sigmoid_interval(
+        n,
+        sigmoid_mix(p.birth.x, p.death.x, m),
+        sigmoid_mix(p.birth.y, p.death.y, m),
+    );
+}
+
+@compute @workgroup_size(8, 8)
+fn cs_main(
+    @builtin(global_invocation_id) id: vec3<u32>,
+) {
+    if id.x >= params.width || id.y >= params.height {
+        return;
+    }
+    let pos = vec2<i32>(id.xy);
+    let radius = i32(ceil(params.smooth_life.outer_radius));
+    let diameter = 2 * radius + 1;
+
+    // Disk filling in x, annulus filling in y
+    var filling = vec2<f32>(0.0);
+    for (var dy = -radius; dy <= radius; dy++) {
+        for (var dx = -radius; dx <= radius; dx++) {
+            let weights = kernel[(dy + radius) * diameter + dx + radius];
+            if any(weights > vec2<f32>(0.0)) {
+                filling += weights * cell(pos + vec2<i32>(dx, dy));
+            }
+        }
+    }
+
+    let s = transition(filling.y, filling.x);
+    var next = s;
+    if params.smooth_life.continuous != 0u {
+        next = clamp(cell(pos) + params.smooth_life.dt * (2.0 * s - 1.0), 0.0, 1.0);
+    }
+    textureStore(cells_dst, pos, vec4<f32>(next, 0.0, 0.0, 1.0));
+}
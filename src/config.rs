@@ -0,0 +1,504 @@
+use crate::{
+    cli,
+    compute::{
+        initial::{InitialCondition, InitialKind},
+        lenia::Creature,
+        simulation::ComputeBackend,
+    },
+    pattern::image::ImageMapping,
+    render::{
+        brush::{self, BrushShape},
+        colormap::Colormap,
+        playback,
+    },
+    shared::{
+        boundary::Boundary,
+        sim_params::{
+            GrayScottParams, LeniaParams, PhysarumParams, SimulationMode, SimulationParams,
+            SmoothLifeParams,
+        },
+    },
+};
+use anyhow::{bail, Context, Result};
+use serde::{de::Error as _, Deserialize, Deserializer};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Settings of a run, read from a TOML file and overridden by command-line flags.
+/// Missing keys keep their defaults and unknown keys are errors
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct Config {
+    pub grid: GridConfig,
+    pub simulation: SimulationConfig,
+    pub initial: InitialConfig,
+    pub window: WindowConfig,
+    /// Changes to the creature's parameters
+    pub lenia: LeniaConfig,
+    pub physarum: PhysarumConfig,
+    pub gray_scott: GrayScottConfig,
+    pub smooth_life: SmoothLifeConfig,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct GridConfig {
+    pub width: u32,
+    pub height: u32,
+    #[serde(deserialize_with = "from_str")]
+    pub boundary: Boundary,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct SimulationConfig {
+    #[serde(deserialize_with = "from_str")]
+    pub mode: SimulationMode,
+    /// Rule of Life or Larger than Life, in the syntax of `--rule`
+    pub rule: Option<String>,
+    /// Discrete time SmoothLife instead of the time-continuous variant
+    pub discrete: bool,
+    #[serde(deserialize_with = "from_str")]
+    pub backend: ComputeBackend,
+    /// Comma separated graphics APIs, as `--graphics`
+    #[serde(deserialize_with = "graphics_backends")]
+    pub graphics: wgpu::Backends,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct InitialConfig {
+    /// Seed of random initial states
+    pub seed: u64,
+    #[serde(deserialize_with = "from_str")]
+    pub kind: InitialKind,
+    pub density: f32,
+    pub extent: f32,
+    pub noise_scale: f32,
+    /// Lenia creature to start from
+    pub creature: String,
+    /// File of cells to start from, relative to the configuration file
+    pub pattern: Option<PathBuf>,
+    /// Cell to place the top left of a stamped pattern at, centred if unset
+    pub pattern_position: Option<[i32; 2]>,
+    /// How the pixels of a PNG pattern become cells
+    #[serde(deserialize_with = "from_str")]
+    pub image_mapping: ImageMapping,
+    /// Snapshot to continue from, relative to the configuration file.
+    /// Its grid, parameters and state replace every other setting of the simulation
+    pub resume: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct WindowConfig {
+    pub title: String,
+    #[serde(deserialize_with = "present_mode")]
+    pub present_mode: wgpu::PresentMode,
+    pub tick_rate: u32,
+    /// Generations stepped at once with shift and period
+    pub step_batch: u32,
+    pub brush_radius: u32,
+    #[serde(deserialize_with = "from_str")]
+    pub brush_shape: BrushShape,
+    #[serde(deserialize_with = "from_str")]
+    pub colormap: Colormap,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct LeniaConfig {
+    pub radius: Option<u32>,
+    pub mu: Option<f32>,
+    pub sigma: Option<f32>,
+    pub dt: Option<f32>,
+    pub peaks: Option<Vec<f32>>,
+}
+
+/// Angles in radians, as `PhysarumParams`
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct PhysarumConfig {
+    pub sensor_angle: Option<f32>,
+    pub sensor_distance: Option<f32>,
+    pub turn_speed: Option<f32>,
+    pub move_speed: Option<f32>,
+    pub deposit: Option<f32>,
+    pub decay: Option<f32>,
+    pub diffuse: Option<f32>,
+    pub agent_count: Option<u32>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct GrayScottConfig {
+    pub feed: Option<f32>,
+    pub kill: Option<f32>,
+    pub diffusion_u: Option<f32>,
+    pub diffusion_v: Option<f32>,
+    pub dt: Option<f32>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct SmoothLifeConfig {
+    pub inner_radius: Option<f32>,
+    pub outer_radius: Option<f32>,
+    pub birth: Option<[f32; 2]>,
+    pub death: Option<[f32; 2]>,
+    pub alpha_n: Option<f32>,
+    pub alpha_m: Option<f32>,
+    pub dt: Option<f32>,
+}
+
+/// Replaces the fields of `params` that are set in `config`
+macro_rules! overlay {
+    ($config:expr, $params:expr, $($field:ident),+) => {
+        $(if let Some(value) = $config.$field {
+            $params.$field = value;
+        })+
+    };
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut config: Self =
+            toml::from_str(&text).with_context(|| format!("Invalid config {}", path.display()))?;
+        if config.grid.width == 0 || config.grid.height == 0 {
+            bail!(
+                "Invalid config {}: grid must be at least one cell wide and high",
+                path.display()
+            );
+        }
+        if let Some(dir) = path.parent() {
+            for file in [&mut config.initial.pattern, &mut config.initial.resume]
+                .into_iter()
+                .flatten()
+            {
+                *file = dir.join(&*file);
+            }
+        }
+        Ok(config)
+    }
+
+    /// The Lenia creature to start from, in Lenia mode
+    pub fn creature(&self) -> Result<Option<&'static Creature>> {
+        match self.simulation.mode {
+            SimulationMode::Lenia => Creature::find(&self.initial.creature).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn initial_condition(&self) -> InitialCondition {
+        InitialCondition {
+            kind: self.initial.kind,
+            density: self.initial.density,
+            extent: self.initial.extent,
+            scale: self.initial.noise_scale,
+        }
+    }
+
+    pub fn params(&self) -> Result<SimulationParams> {
+        let size = winit::dpi::PhysicalSize::new(self.grid.width, self.grid.height);
+        let mut params = SimulationParams::new(&size, self.simulation.mode);
+        params.set_boundary(self.grid.boundary);
+        if let Some(creature) = self.creature()? {
+            params.lenia = creature.params()?;
+        }
+        if let Some(rule) = &self.simulation.rule {
+            params.set_rule(rule)?;
+        }
+        params.lenia = self.lenia.apply(&params.lenia)?;
+        self.physarum.apply(&mut params.physarum)?;
+        self.gray_scott.apply(&mut params.gray_scott);
+        self.smooth_life.apply(&mut params.smooth_life);
+        params.smooth_life.continuous = !self.simulation.discrete as u32;
+        Ok(params)
+    }
+}
+
+impl LeniaConfig {
+    fn apply(&self, params: &LeniaParams) -> Result<LeniaParams> {
+        LeniaParams::new(
+            self.radius.unwrap_or(params.radius),
+            self.mu.unwrap_or(params.mu),
+            self.sigma.unwrap_or(params.sigma),
+            self.dt.unwrap_or(params.dt),
+            self.peaks.as_deref().unwrap_or(params.peaks()),
+        )
+    }
+}
+
+impl PhysarumConfig {
+    fn apply(&self, params: &mut PhysarumParams) -> Result<()> {
+        if self.agent_count == Some(0) {
+            bail!("Physarum needs at least one agent, found agent_count = 0");
+        }
+        overlay!(
+            self,
+            params,
+            sensor_angle,
+            sensor_distance,
+            turn_speed,
+            move_speed,
+            deposit,
+            decay,
+            diffuse,
+            agent_count
+        );
+        Ok(())
+    }
+}
+
+impl GrayScottConfig {
+    fn apply(&self, params: &mut GrayScottParams) {
+        overlay!(self, params, feed, kill, diffusion_u, diffusion_v, dt);
+    }
+}
+
+impl SmoothLifeConfig {
+    fn apply(&self, params: &mut SmoothLifeParams) {
+        overlay!(
+            self,
+            params,
+            inner_radius,
+            outer_radius,
+            birth,
+            death,
+            alpha_n,
+            alpha_m,
+            dt
+        );
+    }
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
+        Self {
+            width: 512,
+            height: 512,
+            boundary: Boundary::default(),
+        }
+    }
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            mode: SimulationMode::default(),
+            rule: None,
+            discrete: false,
+            backend: ComputeBackend::default(),
+            graphics: wgpu::Backends::all(),
+        }
+    }
+}
+
+impl Default for InitialConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            kind: InitialKind::default(),
+            density: 0.5,
+            extent: 0.25,
+            noise_scale: 32.0,
+            creature: "orbium".to_string(),
+            pattern: None,
+            pattern_position: None,
+            image_mapping: ImageMapping::default(),
+            resume: None,
+        }
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: "Cells".to_string(),
+            present_mode: wgpu::PresentMode::Fifo,
+            tick_rate: playback::DEFAULT_TICK_RATE,
+            step_batch: playback::DEFAULT_BATCH,
+            brush_radius: brush::DEFAULT_RADIUS,
+            brush_shape: BrushShape::default(),
+            colormap: Colormap::default(),
+        }
+    }
+}
+
+/// Deserializes a string with `parse`, reporting its error with the key's position
+fn parse_with<'de, D: Deserializer<'de>, T>(
+    deserializer: D,
+    parse: impl FnOnce(&str) -> Result<T>,
+) -> Result<T, D::Error> {
+    let string = String::deserialize(deserializer)?;
+    parse(&string).map_err(|error| D::Error::custom(format!("{error:#}")))
+}
+
+fn from_str<'de, D: Deserializer<'de>, T: FromStr<Err = anyhow::Error>>(
+    deserializer: D,
+) -> Result<T, D::Error> {
+    parse_with(deserializer, str::parse)
+}
+
+fn present_mode<'de, D: Deserializer<'de>>(deserializer: D) -> Result<wgpu::PresentMode, D::Error> {
+    parse_with(deserializer, cli::parse_present_mode)
+}
+
+fn graphics_backends<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<wgpu::Backends, D::Error> {
+    parse_with(deserializer, cli::parse_graphics_backends)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Cli, Command};
+    use clap::Parser;
+
+    /// Writes `text` as a config file in a directory of its own, named after the test
+    fn config_file(name: &str, text: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cells-config-{}-{name}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("cells.toml");
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    fn remove(path: &Path) {
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    /// The configuration of `cells` run with `args`
+    fn run_config(args: &[&str]) -> Result<Config> {
+        let cli = Cli::try_parse_from(std::iter::once("cells").chain(args.iter().copied()))?;
+        match cli.command() {
+            Command::Run(run) => run.config(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn flags_override_the_file() {
+        let path = config_file(
+            "precedence",
+            r#"
+            [grid]
+            width = 64
+            height = 32
+            boundary = "mirror"
+
+            [simulation]
+            mode = "smooth-life"
+            discrete = true
+
+            [initial]
+            seed = 5
+            density = 0.25
+
+            [window]
+            tick_rate = 30
+            "#,
+        );
+        let path = path.to_str().unwrap();
+
+        let config = run_config(&["--config", path]).unwrap();
+        assert_eq!((config.grid.width, config.grid.height), (64, 32));
+        assert_eq!(config.grid.boundary, Boundary::Mirror);
+        assert_eq!(config.simulation.mode, SimulationMode::SmoothLife);
+        assert!(config.simulation.discrete);
+        assert_eq!(config.params().unwrap().smooth_life.continuous, 0);
+        assert_eq!((config.initial.seed, config.initial.density), (5, 0.25));
+        assert_eq!(config.window.tick_rate, 30);
+        // Keys left out keep their defaults
+        assert_eq!(config.initial.extent, InitialConfig::default().extent);
+        assert_eq!(config.window.title, WindowConfig::default().title);
+
+        let config = run_config(&[
+            "--config",
+            path,
+            "--size",
+            "10x20",
+            "--seed",
+            "9",
+            "--tick-rate",
+            "120",
+            "--no-discrete",
+        ])
+        .unwrap();
+        assert_eq!((config.grid.width, config.grid.height), (10, 20));
+        assert_eq!(config.initial.seed, 9);
+        assert_eq!(config.window.tick_rate, 120);
+        assert!(!config.simulation.discrete);
+        assert_eq!(config.params().unwrap().smooth_life.continuous, 1);
+        // Settings without flags are kept from the file
+        assert_eq!(config.grid.boundary, Boundary::Mirror);
+        assert_eq!(config.initial.density, 0.25);
+        remove(Path::new(path));
+    }
+
+    #[test]
+    fn last_discrete_flag_wins() {
+        let discrete = |args: &[&str]| run_config(args).unwrap().simulation.discrete;
+        assert!(!discrete(&[]));
+        assert!(discrete(&["--discrete"]));
+        assert!(!discrete(&["--no-discrete"]));
+        assert!(discrete(&["--no-discrete", "--discrete"]));
+        assert!(!discrete(&["--discrete", "--no-discrete"]));
+    }
+
+    #[test]
+    fn refuses_unknown_keys() {
+        for (name, text, unknown) in [
+            ("unknown-key", "[grid]\nwdth = 3\n", "wdth"),
+            ("unknown-table", "[grids]\nwidth = 3\n", "grids"),
+            (
+                "unknown-parameter",
+                "[lenia]\nradius = 3\nmux = 0.2\n",
+                "mux",
+            ),
+        ] {
+            let path = config_file(name, text);
+            let error = format!("{:#}", Config::load(&path).err().unwrap());
+            remove(&path);
+            assert!(
+                error.starts_with(&format!("Invalid config {}", path.display()))
+                    && error.contains(&format!("unknown field `{unknown}`")),
+                "{error}"
+            );
+        }
+        let path = config_file("bad-value", "[grid]\nboundary = \"sphere\"\n");
+        let error = format!("{:#}", Config::load(&path).err().unwrap());
+        remove(&path);
+        assert!(error.contains("Unknown boundary 'sphere'"), "{error}");
+    }
+
+    #[test]
+    fn resolves_files_against_the_config_directory() {
+        let absolute = std::env::temp_dir().join("elsewhere.rle");
+        let path = config_file(
+            "paths",
+            &format!(
+                "[initial]\npattern = \"patterns/glider.rle\"\nresume = {:?}\n",
+                absolute.to_str().unwrap()
+            ),
+        );
+        let dir = path.parent().unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(
+            config.initial.pattern,
+            Some(dir.join("patterns").join("glider.rle"))
+        );
+        assert_eq!(config.initial.resume, Some(absolute));
+
+        // Paths given as flags are relative to the working directory, as usual
+        let config =
+            run_config(&["--config", path.to_str().unwrap(), "--pattern", "other.rle"]).unwrap();
+        assert_eq!(config.initial.pattern, Some(PathBuf::from("other.rle")));
+        remove(&path);
+    }
+}
@@ -0,0 +1,149 @@
+use crate::{
+    compute::simulation::Simulation,
+    pattern::{self, Pattern, Region},
+    shared::gpu::Gpu,
+};
+use anyhow::{Context, Result};
+use std::{path::Path, time::Instant};
+
+/// Generations encoded into one submission, waited on before encoding more
+/// so the queue doesn't grow without bound
+const SUBMIT_BATCH: u64 = 64;
+
+/// Advances the simulation by `steps` generations and waits for the GPU to finish them
+fn advance(gpu: &Gpu, simulation: &mut Simulation, steps: u64) {
+    let mut last_report = Instant::now();
+    let mut done = 0;
+    while done < steps {
+        let batch = SUBMIT_BATCH.min(steps - done);
+        let command_buffers: Vec<_> = (0..batch)
+            .map(|_| simulation.step(&gpu.device, &gpu.queue).finish())
+            .collect();
+        gpu.queue.submit(command_buffers);
+        gpu.device.poll(wgpu::Maintain::Wait);
+        done += batch;
+        if last_report.elapsed().as_secs() >= 1 {
+            log::info!("Generation {} of {steps}", done);
+            last_report = Instant::now();
+        }
+    }
+}
+
+/// Advances the simulation by `steps` generations without a window, then writes into `output`
+/// the latest generation as `state.f32`, raw little endian f32 texels in row-major order,
+/// and statistics about it as `stats.toml`
+pub fn run(gpu: &Gpu, simulation: &mut Simulation, steps: u64, output: &Path) -> Result<()> {
+    let start = Instant::now();
+    advance(gpu, simulation, steps);
+    let seconds = start.elapsed().as_secs_f64();
+
+    let cells = simulation.read_cells(&gpu.device, &gpu.queue)?;
+    let texture = &simulation.textures()[simulation.current()];
+    let size = texture.texture.size();
+    let channels = texture.channels();
+
+    std::fs::create_dir_all(output)
+        .with_context(|| format!("Failed to create {}", output.display()))?;
+    let state_path = output.join("state.f32");
+    pattern::write(&state_path, &cells)?;
+
+    // Statistics of the first channel, the cell state or Gray-Scott's U
+    let first_channel = || cells.iter().step_by(channels).copied();
+    let population: f64 = first_channel().map(f64::from).sum();
+    let stats = format!(
+        "generation = {}\n\
+        seed = {}\n\
+        steps = {steps}\n\
+        seconds = {seconds}\n\
+        generations_per_second = {}\n\
+        width = {}\n\
+        height = {}\n\
+        channels = {channels}\n\
+        population = {population}\n\
+        alive = {}\n\
+        mean = {}\n\
+        min = {}\n\
+        max = {}\n",
+        simulation.generation,
+        simulation.seed,
+        steps as f64 / seconds,
+        size.width,
+        size.height,
+        first_channel().filter(|&cell| cell >= 0.5).count(),
+        population / (size.width as f64 * size.height as f64),
+        first_channel().fold(f32::INFINITY, f32::min),
+        first_channel().fold(f32::NEG_INFINITY, f32::max),
+    );
+    let stats_path = output.join("stats.toml");
+    std::fs::write(&stats_path, &stats)
+        .with_context(|| format!("Failed to write {}", stats_path.display()))?;
+    log::info!(
+        "Wrote {} and {} after {steps} generations in {seconds:.2} s",
+        state_path.display(),
+        stats_path.display()
+    );
+    Ok(())
+}
+
+/// Writes the numbered states of the latest generation, or of a region of it,
+/// in the pattern format of the path's extension, or its raw values into PNG images
+/// of `bit_depth` bits per sample, with the last state of discrete modes as white
+pub fn export(
+    gpu: &Gpu,
+    simulation: &Simulation,
+    rule: Option<String>,
+    path: &Path,
+    region: Option<Region>,
+    bit_depth: u8,
+) -> Result<()> {
+    let cells = simulation.read_cells(&gpu.device, &gpu.queue)?;
+    let texture = &simulation.textures()[simulation.current()];
+    if pattern::Format::from_path(path)? == pattern::Format::Png {
+        pattern::image::write(
+            path,
+            &cells,
+            texture.texture.width(),
+            texture.channels(),
+            region,
+            bit_depth,
+            simulation.params().white_value(),
+        )?;
+        log::info!("Exported {}", path.display());
+        return Ok(());
+    }
+    let mode = simulation.mode();
+    let mut states = Pattern::from_cells(
+        &cells,
+        texture.texture.width(),
+        texture.channels(),
+        |texel| mode.cell_state(texel),
+    );
+    states.rule = rule;
+    states.comments.push(format!(
+        "Generation {} of {mode:?} from seed {}",
+        simulation.generation, simulation.seed
+    ));
+    if let Some(region) = region {
+        states = states.crop(region)?;
+    }
+    pattern::write_pattern(path, &states)?;
+    log::info!("Exported {}", path.display());
+    Ok(())
+}
+
+/// Times `steps` generations after `warmup` untimed ones and prints the rates to stdout
+pub fn bench(gpu: &Gpu, simulation: &mut Simulation, steps: u64, warmup: u64) {
+    advance(gpu, simulation, warmup);
+    let start = Instant::now();
+    advance(gpu, simulation, steps);
+    let seconds = start.elapsed().as_secs_f64();
+
+    let size = simulation.textures()[0].texture.size();
+    let generations_per_second = steps as f64 / seconds;
+    println!("{steps} generations in {seconds:.3} s");
+    println!("{generations_per_second:.1} generations per second");
+    println!(
+        "{:.3e} cell updates per second",
+        generations_per_second * size.width as f64 * size.height as f64
+    );
+}
@@ -1,27 +1,255 @@
+mod cli;
 mod compute;
+mod config;
+mod headless;
+mod pattern;
 mod render;
 mod shared;
+mod snapshot;
 
-use render::{renderer, window};
+use anyhow::Context;
+use clap::Parser;
+use cli::{Cli, Command, RunArgs};
+use compute::simulation::Simulation;
+use config::Config;
+use pattern::image::ImageMapping;
+use render::{brush::Brush, playback::Playback, renderer, window};
 use shared::{
-    sim_params::{SimulationParams, SimulationParamsBuf},
-    texture,
+    gpu::Gpu,
+    sim_params::{SimulationMode, SimulationParamsBuf},
 };
+use snapshot::Snapshot;
 
-#[tokio::main]
-async fn main() {
-    let window = window::WindowData::new("Cells").await;
-    let cell_texture =
-        texture::Texture::new(&window.device, &window.size, wgpu::TextureFormat::R32Float);
-    let simulation_params =
-        SimulationParamsBuf::new(&window.device, SimulationParams::new(&window.size));
+/// Sets up the simulation on the device, starting from the initial condition,
+/// Lenia creature or pattern file if any, or continuing from a snapshot
+fn new_simulation(gpu: &Gpu, config: &Config) -> anyhow::Result<(SimulationParamsBuf, Simulation)> {
+    if let Some(path) = &config.initial.resume {
+        let snapshot = Snapshot::load(path)?;
+        let simulation_params = SimulationParamsBuf::new(&gpu.device, snapshot.params);
+        let mut simulation = Simulation::new(
+            &gpu.device,
+            &gpu.queue,
+            &gpu.adapter,
+            &simulation_params,
+            snapshot.seed,
+            config.simulation.backend,
+        )?;
+        snapshot.restore(&gpu.queue, &mut simulation)?;
+        log::info!(
+            "Resumed {:?} at generation {} from {}",
+            simulation.mode(),
+            simulation.generation,
+            path.display()
+        );
+        return Ok((simulation_params, simulation));
+    }
+    let stamped = match &config.initial.pattern {
+        Some(path)
+            if !matches!(
+                pattern::Format::from_path(path)?,
+                pattern::Format::RawState | pattern::Format::Png
+            ) =>
+        {
+            let grid = [config.grid.width, config.grid.height];
+            Some(pattern::read_pattern(path, None, Some(grid))?)
+        }
+        _ => None,
+    };
+    let mut params = config.params()?;
+    // Without a rule of their own, patterns run under the rule they were written for
+    let pattern_rule = stamped.as_ref().and_then(|stamped| stamped.rule.as_deref());
+    if let (None, Some(rule), Some(_)) =
+        (&config.simulation.rule, pattern_rule, params.rule_string())
+    {
+        // Golly appends the shape of bounded grids after a colon
+        let rule = rule.split(':').next().unwrap_or_default();
+        params
+            .set_rule(rule)
+            .context("Unsupported rule of the pattern, pass another with --rule")?;
+    }
+    let simulation_params = SimulationParamsBuf::new(&gpu.device, params);
+    let mut simulation = Simulation::new(
+        &gpu.device,
+        &gpu.queue,
+        &gpu.adapter,
+        &simulation_params,
+        config.initial.seed,
+        config.simulation.backend,
+    )?;
+    if let Some(creature) = config.creature()? {
+        simulation.write_cells(&gpu.queue, &creature.stamp(params.width, params.height));
+    }
+    let channels = simulation.textures()[0].channels();
+    let seed = config.initial.seed;
+    if let Some(cells) = config.initial_condition().cells(
+        params.mode(),
+        params.width,
+        params.height,
+        channels,
+        seed,
+    )? {
+        simulation.write_cells(&gpu.queue, &cells);
+    }
+    log::info!("Seeded with {seed}");
+    if let Some(stamped) = stamped {
+        // Centred unless placed
+        let origin = config.initial.pattern_position.unwrap_or([
+            (params.width as i32 - stamped.width as i32) / 2,
+            (params.height as i32 - stamped.height as i32) / 2,
+        ]);
+        simulation.stamp(&gpu.queue, origin, stamped.width, &stamped.states()?);
+    } else if let Some(path) = config
+        .initial
+        .pattern
+        .as_ref()
+        .filter(|path| pattern::Format::from_path(path).ok() == Some(pattern::Format::Png))
+    {
+        let mapping = config.initial.image_mapping;
+        if mapping == ImageMapping::Luminance
+            && matches!(
+                params.mode(),
+                SimulationMode::Life | SimulationMode::LargerThanLife
+            )
+        {
+            anyhow::bail!(
+                "{:?} cells are discrete, map images into them with a threshold",
+                params.mode()
+            );
+        }
+        let image = pattern::image::read(path)?;
+        let values: Vec<f32> = image
+            .luminance
+            .iter()
+            .map(|&luminance| mapping.value(luminance))
+            .collect();
+        // Centred unless placed
+        let origin = config.initial.pattern_position.unwrap_or([
+            (params.width as i32 - image.width as i32) / 2,
+            (params.height as i32 - image.height as i32) / 2,
+        ]);
+        simulation.stamp_values(&gpu.queue, origin, image.width, &values);
+    } else if let Some(path) = &config.initial.pattern {
+        let cells = pattern::read(path, params.width, params.height, channels)?;
+        simulation.write_cells(&gpu.queue, &cells);
+    }
+    Ok((simulation_params, simulation))
+}
+
+/// Device without a window, falling back to a software adapter.
+/// The CPU backend needs one too, since it uploads every generation into textures
+async fn headless_gpu(config: &Config) -> anyhow::Result<Gpu> {
+    Gpu::new(Gpu::instance(config.simulation.graphics), None)
+        .await
+        .context(
+            "Simulating needs an adapter even with --backend cpu, \
+            install a software one such as lavapipe or WARP",
+        )
+}
+
+async fn run(args: RunArgs) -> anyhow::Result<()> {
+    // Validated before opening the window
+    let config = args.config()?;
+    let brush = Brush::new(config.window.brush_radius, config.window.brush_shape)?;
+    let playback = Playback::new(config.window.tick_rate, config.window.step_batch)?;
+
+    let window_data = window::WindowData::new(
+        &config.window.title,
+        config.window.present_mode,
+        config.simulation.graphics,
+    )
+    .await?;
+    let (simulation_params, simulation) = new_simulation(&window_data.gpu, &config)?;
     let renderer = renderer::Renderer::new(
-        &window.device,
-        &cell_texture,
+        &window_data.gpu.device,
+        simulation.textures(),
         &simulation_params,
-        &window.surface_config,
+        &window_data.surface_config,
+        config.window.colormap,
     );
     // Can access through closure arguments the window data
     // needs to be passed shared and simulation arguments by reference
-    window::run(window, renderer);
+    window::run(window_data, renderer, simulation, brush, playback);
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("cells=info,warn"))
+        .init();
+
+    match Cli::parse().command() {
+        Command::Run(args) => run(args).await,
+        Command::Headless {
+            simulation,
+            steps,
+            output,
+            export,
+            region,
+            bit_depth,
+            snapshot,
+        } => {
+            let config = simulation.config()?;
+            let gpu = headless_gpu(&config).await?;
+            let (simulation_params, mut simulation) = new_simulation(&gpu, &config)?;
+            headless::run(&gpu, &mut simulation, steps, &output)?;
+            if let Some(path) = snapshot {
+                Snapshot::capture(&gpu.device, &gpu.queue, &simulation)?.save(&path)?;
+                log::info!("Saved snapshot {}", path.display());
+            }
+            match export {
+                Some(path) => headless::export(
+                    &gpu,
+                    &simulation,
+                    simulation_params.params.rule_string(),
+                    &path,
+                    region,
+                    bit_depth,
+                ),
+                None => Ok(()),
+            }
+        }
+        Command::Convert {
+            input,
+            output,
+            size,
+            channels,
+            region,
+        } => {
+            use pattern::Format::RawState;
+            let formats = (
+                pattern::Format::from_path(&input)?,
+                pattern::Format::from_path(&output)?,
+            );
+            let size = match size {
+                Some(size) => Some((size.width, size.height, channels)),
+                None if formats.0 == RawState => {
+                    anyhow::bail!("Pass the grid size of the input with --size")
+                }
+                None => None,
+            };
+            if let (Some((width, height, channels)), (RawState, RawState), None) =
+                (size, formats, region)
+            {
+                // Copied texel by texel, keeping every channel
+                let cells = pattern::read(&input, width, height, channels)?;
+                return pattern::write(&output, &cells);
+            }
+            let limit = size.map(|(width, height, _)| [width, height]);
+            let mut stamped = pattern::read_pattern(&input, size, limit)?;
+            if let Some(region) = region {
+                stamped = stamped.crop(region)?;
+            }
+            pattern::write_pattern(&output, &stamped)
+        }
+        Command::Bench {
+            simulation,
+            steps,
+            warmup,
+        } => {
+            let config = simulation.config()?;
+            let gpu = headless_gpu(&config).await?;
+            let (_, mut simulation) = new_simulation(&gpu, &config)?;
+            headless::bench(&gpu, &mut simulation, steps, warmup);
+            Ok(())
+        }
+    }
 }
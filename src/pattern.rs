@@ -0,0 +1,467 @@
+pub mod image;
+pub mod life;
+pub mod macrocell;
+pub mod plaintext;
+pub mod rle;
+
+use crate::shared::sim_params::SimulationMode;
+use anyhow::{anyhow, bail, Context, Result};
+use std::{fmt, path::Path, str::FromStr};
+
+/// Most cells expanded from runs, quadtrees or bounding boxes,
+/// which can describe far more than fit in memory
+const MAX_CELLS: u64 = 1 << 26;
+
+/// File formats holding cells, recognized by their extension
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Raw little endian f32 texels in row-major order, as written by headless runs.
+    /// The grid size is not stored and must be known when reading
+    RawState,
+    /// Golly's run length encoded patterns: a header like `x = 3, y = 3, rule = B3/S23`
+    /// followed by runs of states, rows ending in `$` and the pattern in `!`.
+    /// Two state patterns use `b` for dead and `o` for alive, patterns with more states
+    /// use `.` for dead and `A` to `X`, then `pA` to `yO`, for states 1 to 255
+    Rle,
+    /// LifeWiki's plaintext drawings: `!` starts comment lines, the first of which may be
+    /// `!Name: ...`, and the other lines draw rows of cells with `.` for dead and `O` for alive
+    Plaintext,
+    /// Life 1.05 or 1.06, told apart by their first line and written as Life 1.06.
+    /// Life 1.05 draws blocks of rows with `.` for dead and `*` for alive, each starting at the
+    /// cell of the `#P x y` line before it, with `#D` descriptions and `#N` or
+    /// `#R survival/birth` rules. Life 1.06 lists the `x y` of alive cells, one per line
+    Life,
+    /// Golly's macrocell quadtrees, for huge patterns. After the `[M2]` header, each line is
+    /// a node numbered from 1: an 8x8 leaf of `.` and `*` rows ending in `$` for two states,
+    /// or `level nw ne sw se` with the states of a 2x2 square at level 1 and the numbers of
+    /// its quarters above, 0 for empty ones. The last node is the root, centred on cell (0, 0)
+    Macrocell,
+    /// PNG images, whose luminance is mapped to cell values when read
+    /// and which hold the raw values of a grid when written
+    Png,
+}
+
+/// Cells of a pattern file, apart from the grid they are placed into
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Pattern {
+    /// Cells in states other than dead, at coordinates that may be negative
+    pub cells: Vec<([i32; 2], u8)>,
+    /// Top left cell of the bounding box
+    pub origin: [i32; 2],
+    /// Size of the bounding box, which covers every cell and may be larger
+    pub width: u32,
+    pub height: u32,
+    pub rule: Option<String>,
+    pub name: Option<String>,
+    pub comments: Vec<String>,
+}
+
+/// A rectangle of cells, from its top left cell
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Format {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+        Ok(match extension.to_ascii_lowercase().as_str() {
+            "f32" => Self::RawState,
+            "rle" => Self::Rle,
+            "cells" => Self::Plaintext,
+            "lif" | "life" => Self::Life,
+            "mc" => Self::Macrocell,
+            "png" => Self::Png,
+            _ => bail!(
+                "Unknown pattern format of {}, \
+                expected a .f32, .rle, .cells, .lif, .mc or .png extension",
+                path.display()
+            ),
+        })
+    }
+}
+
+/// Reads a grid of `width` by `height` cells of `channels` f32 each, in row-major order
+pub fn read(path: &Path, width: u32, height: u32, channels: usize) -> Result<Vec<f32>> {
+    let read = || -> Result<_> {
+        match Format::from_path(path)? {
+            Format::RawState => {
+                let bytes = std::fs::read(path)?;
+                let expected = width as usize * height as usize * channels;
+                if bytes.len() != expected * std::mem::size_of::<f32>() {
+                    bail!(
+                        "Expected {expected} f32 values for a {width}x{height} grid \
+                        with {channels} channels, found {} bytes",
+                        bytes.len()
+                    );
+                }
+                Ok(bytes
+                    .chunks_exact(std::mem::size_of::<f32>())
+                    .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+                    .collect())
+            }
+            _ => bail!("Patterns hold states to stamp, not a whole grid"),
+        }
+    };
+    read().with_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// Writes a grid of cells in row-major order
+pub fn write(path: &Path, cells: &[f32]) -> Result<()> {
+    let write = || -> Result<_> {
+        match Format::from_path(path)? {
+            Format::RawState => {
+                let bytes: Vec<u8> = cells.iter().flat_map(|cell| cell.to_le_bytes()).collect();
+                std::fs::write(path, bytes)?;
+            }
+            _ => bail!("Patterns hold states, not texels"),
+        }
+        Ok(())
+    };
+    write().with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Reads a pattern of numbered states. Raw grids need their size and channels,
+/// and are numbered from the first channel as in Life.
+/// Macrocells wider or higher than `limit` are refused rather than expanded
+pub fn read_pattern(
+    path: &Path,
+    size: Option<(u32, u32, usize)>,
+    limit: Option<[u32; 2]>,
+) -> Result<Pattern> {
+    let format = Format::from_path(path)?;
+    if format == Format::RawState {
+        let (width, height, channels) =
+            size.ok_or_else(|| anyhow!("The grid size of {} is not stored in it", path.display()))?;
+        let cells = read(path, width, height, channels)?;
+        return Ok(Pattern::from_cells(&cells, width, channels, |texel| {
+            SimulationMode::Life.cell_state(texel)
+        }));
+    }
+    if format == Format::Png {
+        bail!(
+            "{} holds cell values rather than numbered states",
+            path.display()
+        );
+    }
+    let read = || -> Result<Pattern> {
+        let text = std::fs::read_to_string(path)?;
+        match format {
+            Format::Rle => rle::parse(&text),
+            Format::Plaintext => plaintext::parse(&text),
+            Format::Life => life::parse(&text),
+            Format::Macrocell => macrocell::parse(&text, limit),
+            Format::RawState | Format::Png => unreachable!(),
+        }
+    };
+    read().with_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// Writes a pattern of numbered states, as one f32 per cell of its bounding box in raw grids
+pub fn write_pattern(path: &Path, pattern: &Pattern) -> Result<()> {
+    let text = match Format::from_path(path)? {
+        Format::RawState => {
+            let cells: Vec<f32> = pattern.states()?.into_iter().map(f32::from).collect();
+            return write(path, &cells);
+        }
+        Format::Rle => rle::format(pattern),
+        Format::Plaintext => plaintext::format(pattern),
+        Format::Life => life::format(pattern),
+        Format::Macrocell => macrocell::format(pattern),
+        Format::Png => Err(anyhow!(
+            "Images hold cell values rather than numbered states"
+        )),
+    };
+    let write = || -> Result<()> { Ok(std::fs::write(path, text?)?) };
+    write().with_context(|| format!("Failed to write {}", path.display()))
+}
+
+impl Pattern {
+    /// The pattern of a whole grid, numbering each texel of `channels` f32 with `state`
+    pub fn from_cells(
+        cells: &[f32],
+        width: u32,
+        channels: usize,
+        state: impl Fn(&[f32]) -> u8,
+    ) -> Self {
+        let texels = cells.chunks_exact(channels);
+        let height = (texels.len() / width as usize) as u32;
+        let cells = texels
+            .enumerate()
+            .map(|(index, texel)| (index as u32, state(texel)))
+            .filter(|&(_, state)| state > 0)
+            .map(|(index, state)| ([(index % width) as i32, (index / width) as i32], state))
+            .collect();
+        Self {
+            cells,
+            width,
+            height,
+            ..Default::default()
+        }
+    }
+
+    /// Builds the pattern of some cells, with the bounding box fitting them
+    /// and, if given, the declared box of the file, which must lie within the cells of a grid
+    pub fn with_cells(
+        cells: Vec<([i32; 2], u8)>,
+        declared: Option<([i32; 2], [u32; 2])>,
+    ) -> Result<Self> {
+        let mut pattern = Self {
+            cells,
+            ..Default::default()
+        };
+        let mut min = [i64::MAX; 2];
+        let mut max = [i64::MIN; 2];
+        if let Some((origin, size)) = declared {
+            for axis in 0..2 {
+                let end = i64::from(origin[axis]) + i64::from(size[axis]) - 1;
+                if size[axis] > i32::MAX as u32 || end > i64::from(i32::MAX) {
+                    bail!(
+                        "The declared {}x{} box at {},{} lies beyond the cells of a grid",
+                        size[0],
+                        size[1],
+                        origin[0],
+                        origin[1]
+                    );
+                }
+                min[axis] = i64::from(origin[axis]);
+                max[axis] = end;
+            }
+        }
+        for &([x, y], _) in &pattern.cells {
+            min = [min[0].min(x.into()), min[1].min(y.into())];
+            max = [max[0].max(x.into()), max[1].max(y.into())];
+        }
+        if min[0] <= max[0] && min[1] <= max[1] {
+            let extent = [max[0] - min[0] + 1, max[1] - min[1] + 1];
+            let [Ok(width), Ok(height)] = extent.map(u32::try_from) else {
+                bail!(
+                    "The pattern spans {}x{} cells, more than a grid holds",
+                    extent[0],
+                    extent[1]
+                );
+            };
+            // The top left is a cell or the declared origin
+            pattern.origin = min.map(|coordinate| coordinate as i32);
+            pattern.width = width;
+            pattern.height = height;
+        }
+        Ok(pattern)
+    }
+
+    /// States of the bounding box in row-major order,
+    /// refusing boxes of more than [`MAX_CELLS`] cells
+    pub fn states(&self) -> Result<Vec<u8>> {
+        let count = u64::from(self.width) * u64::from(self.height);
+        if count > MAX_CELLS {
+            bail!(
+                "The {}x{} bounding box of the pattern has more than the {MAX_CELLS} cells \
+                spelled out at most",
+                self.width,
+                self.height
+            );
+        }
+        let mut states = vec![0; count as usize];
+        for &([x, y], state) in &self.cells {
+            let x = (i64::from(x) - i64::from(self.origin[0])) as usize;
+            let y = (i64::from(y) - i64::from(self.origin[1])) as usize;
+            states[y * self.width as usize + x] = state;
+        }
+        Ok(states)
+    }
+
+    /// Highest state of any cell, 0 for an empty pattern
+    pub fn max_state(&self) -> u8 {
+        self.cells
+            .iter()
+            .map(|&(_, state)| state)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The cells within `region` of the bounding box, keeping the metadata
+    pub fn crop(&self, region: Region) -> Result<Self> {
+        if !region.fits(self.width, self.height) {
+            bail!(
+                "Region {region} is outside the {}x{} pattern",
+                self.width,
+                self.height
+            );
+        }
+        // The region lies within the bounding box, whose cells all fit in i32
+        let origin = [
+            i64::from(self.origin[0]) + i64::from(region.x),
+            i64::from(self.origin[1]) + i64::from(region.y),
+        ];
+        let within = |[x, y]: [i32; 2]| {
+            (origin[0]..origin[0] + i64::from(region.width)).contains(&x.into())
+                && (origin[1]..origin[1] + i64::from(region.height)).contains(&y.into())
+        };
+        Ok(Self {
+            cells: self
+                .cells
+                .iter()
+                .copied()
+                .filter(|&(cell, _)| within(cell))
+                .collect(),
+            origin: origin.map(|coordinate| coordinate as i32),
+            width: region.width,
+            height: region.height,
+            ..self.clone()
+        })
+    }
+
+    /// Fails for patterns of more states than the format holds
+    fn check_two_states(&self, format: &str) -> Result<()> {
+        if self.max_state() > 1 {
+            bail!(
+                "{format} files hold two states, but the pattern has up to {}",
+                u32::from(self.max_state()) + 1
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Region {
+    /// Whether the region lies within a `width` by `height` rectangle, without overflowing
+    pub fn fits(&self, width: u32, height: u32) -> bool {
+        u64::from(self.x) + u64::from(self.width) <= u64::from(width)
+            && u64::from(self.y) + u64::from(self.height) <= u64::from(height)
+    }
+}
+
+impl FromStr for Region {
+    type Err = anyhow::Error;
+
+    /// Parses `X,Y,WIDTHxHEIGHT`
+    fn from_str(region: &str) -> Result<Self> {
+        let parse = || -> Result<Self> {
+            let mut parts = region.splitn(3, ',');
+            let mut next = || {
+                parts
+                    .next()
+                    .ok_or_else(|| anyhow!("Expected X,Y,WIDTHxHEIGHT"))
+            };
+            let (x, y, size) = (next()?, next()?, next()?);
+            let (width, height) = size
+                .split_once('x')
+                .ok_or_else(|| anyhow!("Expected WIDTHxHEIGHT, found '{size}'"))?;
+            let region = Self {
+                x: x.trim().parse()?,
+                y: y.trim().parse()?,
+                width: width.trim().parse()?,
+                height: height.trim().parse()?,
+            };
+            if region.width == 0 || region.height == 0 {
+                bail!("Region must be at least one cell wide and high");
+            }
+            Ok(region)
+        };
+        parse().map_err(|error| anyhow!("Invalid region '{region}': {error:#}"))
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{},{}x{}", self.x, self.y, self.width, self.height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glider() -> Pattern {
+        Pattern::with_cells(
+            vec![
+                ([1, 0], 1),
+                ([2, 1], 1),
+                ([0, 2], 1),
+                ([1, 2], 1),
+                ([2, 2], 1),
+            ],
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn crops_within_the_bounding_box() {
+        let region = |region: &str| region.parse::<Region>().unwrap();
+        let crop = glider().crop(region("1,1,2x2")).unwrap();
+        assert_eq!((crop.origin, crop.width, crop.height), ([1, 1], 2, 2));
+        assert_eq!(crop.states().unwrap(), [0, 1, 1, 1]);
+        for outside in ["2,0,2x1", "0,3,1x1", "4294967295,0,1x1", "0,1,1x4294967295"] {
+            assert_eq!(
+                format!("{:#}", glider().crop(region(outside)).unwrap_err()),
+                format!("Region {outside} is outside the 3x3 pattern")
+            );
+        }
+    }
+
+    #[test]
+    fn counts_states_up_to_256() {
+        let pattern = Pattern::with_cells(vec![([0, 0], u8::MAX)], None).unwrap();
+        assert_eq!(
+            format!("{:#}", pattern.check_two_states("Plaintext").unwrap_err()),
+            "Plaintext files hold two states, but the pattern has up to 256"
+        );
+        assert!(glider().check_two_states("Plaintext").is_ok());
+    }
+
+    #[test]
+    fn bounds_boxes_without_overflowing() {
+        let far = Pattern::with_cells(
+            vec![([-2_000_000_000, 0], 1), ([2_000_000_000, 0], 1)],
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            (far.origin, far.width, far.height),
+            ([-2_000_000_000, 0], 4_000_000_001, 1)
+        );
+        assert_eq!(
+            format!(
+                "{:#}",
+                Pattern::with_cells(vec![([i32::MIN, 0], 1), ([i32::MAX, 0], 1)], None)
+                    .unwrap_err()
+            ),
+            "The pattern spans 4294967296x1 cells, more than a grid holds"
+        );
+        for declared in [
+            ([0, 0], [u32::MAX, 1]),
+            ([-5, 1], [1, 2_147_483_648]),
+            ([2, 0], [i32::MAX as u32, 1]),
+        ] {
+            assert!(Pattern::with_cells(Vec::new(), Some(declared)).is_err());
+        }
+        let declared =
+            Pattern::with_cells(vec![([1, 1], 1)], Some(([-3, 0], [i32::MAX as u32, 2]))).unwrap();
+        assert_eq!(
+            (declared.origin, declared.width, declared.height),
+            ([-3, 0], i32::MAX as u32, 2)
+        );
+    }
+
+    #[test]
+    fn refuses_to_spell_out_huge_boxes() {
+        let sparse =
+            Pattern::with_cells(vec![([0, 0], 1), ([1_000_000, 1_000_000], 1)], None).unwrap();
+        assert_eq!(
+            format!("{:#}", sparse.states().unwrap_err()),
+            "The 1000001x1000001 bounding box of the pattern has more than the 67108864 cells \
+            spelled out at most"
+        );
+        assert!(rle::format(&sparse).is_err());
+        assert!(plaintext::format(&sparse).is_err());
+        assert!(life::format(&sparse).is_ok());
+        assert!(macrocell::format(&sparse).is_ok());
+    }
+}
@@ -0,0 +1,213 @@
+use super::Region;
+use anyhow::{anyhow, bail, Context, Result};
+use std::{fs::File, io::BufWriter, path::Path, str::FromStr};
+
+/// Rec. 709 weights of red, green and blue in luminance
+const LUMINANCE_WEIGHTS: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+/// How the luminance of pixels becomes the value of cells, from 0 for dead to 1 for alive
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ImageMapping {
+    /// Cells are alive where pixels are at least this bright
+    Threshold(f32),
+    /// Cells take the luminance as it is, for continuous modes
+    Luminance,
+}
+
+impl Default for ImageMapping {
+    fn default() -> Self {
+        Self::Threshold(0.5)
+    }
+}
+
+impl ImageMapping {
+    pub fn value(&self, luminance: f32) -> f32 {
+        match self {
+            Self::Threshold(threshold) => (luminance >= *threshold) as u8 as f32,
+            Self::Luminance => luminance,
+        }
+    }
+}
+
+/// Luminance of the pixels of an image, from 0 for black to 1 for white
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    /// Row-major, one per pixel
+    pub luminance: Vec<f32>,
+}
+
+/// Reads a grayscale, RGB or indexed PNG of any bit depth, with transparent pixels
+/// blended towards black
+pub fn read(path: &Path) -> Result<Image> {
+    let read = || -> Result<Image> {
+        let mut decoder = png::Decoder::new(File::open(path)?);
+        // Palettes and low bit depths are expanded to 8 bit samples
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let mut reader = decoder.read_info()?;
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf)?;
+        let bytes = &buf[..info.buffer_size()];
+
+        let samples: Vec<f32> = match info.bit_depth {
+            png::BitDepth::Eight => bytes.iter().map(|&sample| sample as f32 / 255.0).collect(),
+            png::BitDepth::Sixteen => bytes
+                .chunks_exact(2)
+                .map(|sample| u16::from_be_bytes([sample[0], sample[1]]) as f32 / 65535.0)
+                .collect(),
+            depth => bail!("Unexpected {depth:?} samples after expansion"),
+        };
+        let luminance = match info.color_type {
+            png::ColorType::Grayscale => samples,
+            png::ColorType::GrayscaleAlpha => samples
+                .chunks_exact(2)
+                .map(|pixel| pixel[0] * pixel[1])
+                .collect(),
+            png::ColorType::Rgb => samples.chunks_exact(3).map(rgb_luminance).collect(),
+            png::ColorType::Rgba => samples
+                .chunks_exact(4)
+                .map(|pixel| rgb_luminance(pixel) * pixel[3])
+                .collect(),
+            color_type => bail!("Unexpected {color_type:?} pixels after expansion"),
+        };
+        Ok(Image {
+            width: info.width,
+            height: info.height,
+            luminance,
+        })
+    };
+    read().with_context(|| format!("Failed to read {}", path.display()))
+}
+
+fn rgb_luminance(pixel: &[f32]) -> f32 {
+    LUMINANCE_WEIGHTS
+        .iter()
+        .zip(pixel)
+        .map(|(weight, sample)| weight * sample)
+        .sum()
+}
+
+/// Writes the raw values of a grid, or of a region of it, of `channels` f32 per cell:
+/// grayscale from single channel cells, and red, green and blue from the first three
+/// channels of others. Values from 0 to `white` span the samples of `bit_depth`,
+/// values beyond are clamped, like Physarum trails brighter than `white`
+pub fn write(
+    path: &Path,
+    cells: &[f32],
+    width: u32,
+    channels: usize,
+    region: Option<Region>,
+    bit_depth: u8,
+    white: f32,
+) -> Result<()> {
+    let write = || -> Result<()> {
+        let height = (cells.len() / channels / width as usize) as u32;
+        let region = region.unwrap_or(Region {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        });
+        if !region.fits(width, height) {
+            bail!("Region {region} is outside the {width}x{height} grid");
+        }
+        let (color_type, samples_per_pixel) = match channels {
+            1 => (png::ColorType::Grayscale, 1),
+            _ => (png::ColorType::Rgb, 3),
+        };
+        let (depth, max_sample) = match bit_depth {
+            8 => (png::BitDepth::Eight, u8::MAX as f32),
+            16 => (png::BitDepth::Sixteen, u16::MAX as f32),
+            _ => bail!("Expected a bit depth of 8 or 16, found {bit_depth}"),
+        };
+
+        let mut data = Vec::new();
+        for y in region.y..region.y + region.height {
+            for x in region.x..region.x + region.width {
+                let texel = (y as usize * width as usize + x as usize) * channels;
+                let texel = &cells[texel..texel + channels];
+                for channel in 0..samples_per_pixel {
+                    let value = texel.get(channel).copied().unwrap_or(0.0);
+                    let sample = ((value / white).clamp(0.0, 1.0) * max_sample).round();
+                    match depth {
+                        png::BitDepth::Eight => data.push(sample as u8),
+                        _ => data.extend((sample as u16).to_be_bytes()),
+                    }
+                }
+            }
+        }
+
+        let mut encoder = png::Encoder::new(
+            BufWriter::new(File::create(path)?),
+            region.width,
+            region.height,
+        );
+        encoder.set_color(color_type);
+        encoder.set_depth(depth);
+        encoder.write_header()?.write_image_data(&data)?;
+        Ok(())
+    };
+    write().with_context(|| format!("Failed to write {}", path.display()))
+}
+
+impl FromStr for ImageMapping {
+    type Err = anyhow::Error;
+
+    /// Parses `luminance`, `threshold` or `threshold=T`
+    fn from_str(mapping: &str) -> Result<Self> {
+        let (kind, threshold) = match mapping.split_once('=') {
+            Some((kind, threshold)) => (kind, Some(threshold)),
+            None => (mapping, None),
+        };
+        let threshold = match (kind.trim().to_ascii_lowercase().as_str(), threshold) {
+            ("luminance", None) => return Ok(Self::Luminance),
+            ("threshold", None) => return Ok(Self::default()),
+            ("threshold", Some(threshold)) => threshold
+                .trim()
+                .parse::<f32>()
+                .map_err(|error| anyhow!("Invalid threshold '{threshold}': {error}"))?,
+            _ => bail!(
+                "Unknown image mapping '{mapping}', \
+                expected threshold, threshold=T or luminance"
+            ),
+        };
+        if !(0.0..=1.0).contains(&threshold) {
+            bail!("Threshold must be within 0 and 1, found {threshold}");
+        }
+        Ok(Self::Threshold(threshold))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Luminance of the cells written into an 8 bit PNG with `white`, as read back
+    fn written(name: &str, cells: &[f32], white: f32) -> Vec<f32> {
+        let path =
+            std::env::temp_dir().join(format!("cells-image-{}-{name}.png", std::process::id()));
+        write(&path, cells, cells.len() as u32, 1, None, 8, white).unwrap();
+        let image = read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        image.luminance
+    }
+
+    #[test]
+    fn shades_every_state_from_black_to_white() {
+        // Dead, alive and the two dying states of a four state Generations rule
+        let luminance = written("states", &[0.0, 1.0, 2.0, 3.0], 3.0);
+        let samples: Vec<u8> = luminance
+            .iter()
+            .map(|value| (value * 255.0).round() as u8)
+            .collect();
+        assert_eq!(samples, [0, 85, 170, 255]);
+    }
+
+    #[test]
+    fn clamps_continuous_values() {
+        assert_eq!(
+            written("clamped", &[-0.5, 0.0, 1.0, 7.5], 1.0),
+            [0.0, 0.0, 1.0, 1.0]
+        );
+    }
+}
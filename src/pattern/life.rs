@@ -0,0 +1,200 @@
+use super::Pattern;
+use anyhow::{anyhow, bail, Context, Result};
+use std::fmt::Write;
+
+const HEADER_105: &str = "#Life 1.05";
+const HEADER_106: &str = "#Life 1.06";
+
+/// Prefix of the description line holding the name, as written by [`format`]
+const NAME_PREFIX: &str = "Name:";
+
+pub fn parse(text: &str) -> Result<Pattern> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()));
+    let (_, header) = lines.next().ok_or_else(|| anyhow!("Line 1: empty file"))?;
+    let version_105 = match header {
+        HEADER_105 => true,
+        HEADER_106 => false,
+        _ => bail!("Line 1: expected '{HEADER_105}' or '{HEADER_106}', found '{header}'"),
+    };
+
+    let mut metadata = Pattern::default();
+    let mut cells = Vec::new();
+    // Top left cell of the current Life 1.05 block, and the row within it of the next line
+    let mut block = [0, 0];
+    let mut row = 0u32;
+    for (number, line) in lines {
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(directive) = line.strip_prefix('#') {
+            let mut chars = directive.chars();
+            let kind = chars.next();
+            let content = chars.as_str().trim();
+            match (kind, version_105) {
+                (Some('D' | 'C'), _) => match content.strip_prefix(NAME_PREFIX) {
+                    Some(name) if metadata.name.is_none() => {
+                        metadata.name = Some(name.trim().into())
+                    }
+                    _ => metadata.comments.push(content.into()),
+                },
+                (Some('N'), true) => metadata.rule = Some("B3/S23".into()),
+                (Some('R'), true) => metadata.rule = Some(content.into()),
+                (Some('P'), true) => {
+                    block = parse_coordinates(content)
+                        .with_context(|| format!("Line {number}: invalid block position"))?;
+                    row = 0;
+                }
+                _ => bail!("Line {number}: unexpected '{line}'"),
+            }
+            continue;
+        }
+
+        if version_105 {
+            for (x, c) in line.chars().enumerate() {
+                match c {
+                    '.' => {}
+                    '*' => {
+                        let x = i32::try_from(x)
+                            .ok()
+                            .and_then(|x| block[0].checked_add(x))
+                            .ok_or_else(|| anyhow!("Line {number}: pattern is too wide"))?;
+                        let y = i32::try_from(row)
+                            .ok()
+                            .and_then(|row| block[1].checked_add(row))
+                            .ok_or_else(|| anyhow!("Line {number}: pattern is too high"))?;
+                        cells.push(([x, y], 1));
+                    }
+                    c => bail!("Line {number}: unexpected '{c}', expected '.' or '*'"),
+                }
+            }
+            row = row.saturating_add(1);
+        } else {
+            let cell = parse_coordinates(line).with_context(|| format!("Line {number}"))?;
+            cells.push((cell, 1));
+        }
+    }
+    Ok(Pattern {
+        rule: metadata.rule,
+        name: metadata.name,
+        comments: metadata.comments,
+        ..Pattern::with_cells(cells, None)?
+    })
+}
+
+/// Parses `x y` coordinates
+fn parse_coordinates(coordinates: &str) -> Result<[i32; 2]> {
+    let mut numbers = coordinates.split_whitespace().map(|number| {
+        number
+            .parse::<i32>()
+            .with_context(|| format!("Expected a coordinate, found '{number}'"))
+    });
+    match (numbers.next(), numbers.next(), numbers.next()) {
+        (Some(x), Some(y), None) => Ok([x?, y?]),
+        _ => bail!("Expected 'x y', found '{coordinates}'"),
+    }
+}
+
+/// Writes Life 1.06, with the name and comments as Life 1.05 style descriptions
+pub fn format(pattern: &Pattern) -> Result<String> {
+    pattern.check_two_states("Life 1.06")?;
+    let mut text = String::new();
+    writeln!(text, "{HEADER_106}")?;
+    if let Some(name) = &pattern.name {
+        writeln!(text, "#D {NAME_PREFIX} {name}")?;
+    }
+    for comment in &pattern.comments {
+        writeln!(text, "#D {comment}")?;
+    }
+    let mut cells: Vec<[i32; 2]> = pattern.cells.iter().map(|&(cell, _)| cell).collect();
+    cells.sort_by_key(|&[x, y]| (y, x));
+    for [x, y] in cells {
+        writeln!(text, "{x} {y}")?;
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> String {
+        format!("{:#}", parse(text).unwrap_err())
+    }
+
+    fn round_trip(pattern: &Pattern) -> Pattern {
+        parse(&format(pattern).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn round_trips_life_106() {
+        let text = "#Life 1.06\n#D Name: Glider\n#D The smallest spaceship\n\
+            0 -1\n1 0\n-1 1\n0 1\n1 1\n";
+        let glider = parse(text).unwrap();
+        assert_eq!(glider.name.as_deref(), Some("Glider"));
+        assert_eq!(glider.comments, ["The smallest spaceship"]);
+        assert_eq!(
+            (glider.origin, glider.width, glider.height),
+            ([-1, -1], 3, 3)
+        );
+        assert_eq!(glider.states().unwrap(), [0, 1, 0, 0, 0, 1, 1, 1, 1]);
+        assert_eq!(format(&glider).unwrap(), text);
+        assert_eq!(round_trip(&glider), glider);
+    }
+
+    #[test]
+    fn reads_life_105_blocks_back_as_life_106() {
+        let text = "#Life 1.05\n#D A glider and a blinker\n#N\n\
+            #P -1 -1\n.*\n..*\n***\n\
+            #P 10 -5\n***\n";
+        let pattern = parse(text).unwrap();
+        assert_eq!(pattern.rule.as_deref(), Some("B3/S23"));
+        assert_eq!(pattern.comments, ["A glider and a blinker"]);
+        assert_eq!(
+            pattern.cells,
+            [
+                ([0, -1], 1),
+                ([1, 0], 1),
+                ([-1, 1], 1),
+                ([0, 1], 1),
+                ([1, 1], 1),
+                ([10, -5], 1),
+                ([11, -5], 1),
+                ([12, -5], 1),
+            ]
+        );
+        assert_eq!(
+            (pattern.origin, pattern.width, pattern.height),
+            ([-1, -5], 14, 7)
+        );
+        // Life 1.06 keeps no rule, and lists cells in row-major order
+        let mut read = round_trip(&pattern);
+        assert_eq!(read.rule, None);
+        read.cells.sort_by_key(|&([x, y], _)| (x, y));
+        let mut cells = pattern.cells.clone();
+        cells.sort_by_key(|&([x, y], _)| (x, y));
+        assert_eq!(read.cells, cells);
+        assert_eq!((read.origin, read.width, read.height), ([-1, -5], 14, 7));
+        assert_eq!(
+            error("#Life 1.05\n#P 0 0\n.o\n"),
+            "Line 3: unexpected 'o', expected '.' or '*'"
+        );
+    }
+
+    #[test]
+    fn refuses_blocks_beyond_the_grid() {
+        assert_eq!(
+            error("#Life 1.05\n#P 2147483647 0\n.*\n"),
+            "Line 3: pattern is too wide"
+        );
+        assert_eq!(
+            error("#Life 1.05\n#P 0 2147483647\n*\n*\n"),
+            "Line 4: pattern is too high"
+        );
+        let edge = parse("#Life 1.05\n#P 2147483646 2147483647\n.*\n").unwrap();
+        assert_eq!(edge.cells, [([i32::MAX, i32::MAX], 1)]);
+        assert!(parse("#Life 1.06\n2147483648 0\n").is_err());
+    }
+}
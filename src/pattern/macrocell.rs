@@ -0,0 +1,454 @@
+use super::{Pattern, MAX_CELLS};
+use anyhow::{anyhow, bail, Context, Result};
+use std::{collections::HashMap, fmt::Write};
+
+/// Deepest level of a node, whose square is 2^63 cells wide
+const MAX_LEVEL: usize = 63;
+
+/// Level of the 8x8 leaves of two state patterns
+const LEAF_LEVEL: usize = 3;
+
+/// A square of 2^level cells, either spelled out or made of four squares half as wide
+struct Node {
+    level: usize,
+    kind: NodeKind,
+    /// Cells in states other than dead
+    population: u64,
+    /// First and last populated cell from the top left of the square, unless empty
+    bounds: Option<[[u64; 2]; 2]>,
+}
+
+enum NodeKind {
+    /// States in row-major order
+    Leaf(Vec<u8>),
+    /// Numbers of the north west, north east, south west and south east squares, 0 for empty
+    Branch([usize; 4]),
+}
+
+impl Node {
+    fn leaf(level: usize, states: Vec<u8>) -> Self {
+        let side = 1 << level;
+        let mut node = Self {
+            level,
+            kind: NodeKind::Branch([0; 4]),
+            population: 0,
+            bounds: None,
+        };
+        for (index, &state) in states.iter().enumerate() {
+            if state > 0 {
+                node.include([(index % side) as u64, (index / side) as u64]);
+                node.population += 1;
+            }
+        }
+        node.kind = NodeKind::Leaf(states);
+        node
+    }
+
+    fn branch(level: usize, children: [usize; 4], nodes: &[Node]) -> Self {
+        let half = 1u64 << (level - 1);
+        let mut node = Self {
+            level,
+            kind: NodeKind::Branch(children),
+            population: 0,
+            bounds: None,
+        };
+        for (quadrant, &child) in children.iter().enumerate() {
+            let Some(child) = child.checked_sub(1).map(|index| &nodes[index]) else {
+                continue;
+            };
+            let offset = [(quadrant % 2) as u64 * half, (quadrant / 2) as u64 * half];
+            if let Some([min, max]) = child.bounds {
+                node.include([min[0] + offset[0], min[1] + offset[1]]);
+                node.include([max[0] + offset[0], max[1] + offset[1]]);
+            }
+            node.population = node.population.saturating_add(child.population);
+        }
+        node
+    }
+
+    /// Grows the bounds to hold `cell`
+    fn include(&mut self, [x, y]: [u64; 2]) {
+        self.bounds = Some(match self.bounds {
+            Some([min, max]) => [
+                [min[0].min(x), min[1].min(y)],
+                [max[0].max(x), max[1].max(y)],
+            ],
+            None => [[x, y], [x, y]],
+        });
+    }
+}
+
+/// Parses `.` and `*` rows ending in `$`, which leave out the dead cells ending rows
+/// and the empty rows ending the square
+fn parse_leaf(line: &str) -> Result<Node> {
+    let side = 1 << LEAF_LEVEL;
+    let mut states = vec![0; side * side];
+    let (mut x, mut y) = (0, 0);
+    for c in line.chars() {
+        match c {
+            '.' => x += 1,
+            '*' => {
+                if x >= side || y >= side {
+                    bail!("Leaf cells lie beyond its {side}x{side} square");
+                }
+                states[y * side + x] = 1;
+                x += 1;
+            }
+            '$' => (x, y) = (0, y + 1),
+            c => bail!("Unexpected '{c}' in a leaf"),
+        }
+    }
+    Ok(Node::leaf(LEAF_LEVEL, states))
+}
+
+/// Parses `level nw ne sw se`: states at level 1, and numbers of earlier nodes above
+fn parse_node(line: &str, nodes: &[Node]) -> Result<Node> {
+    let numbers = line
+        .split_whitespace()
+        .map(|number| {
+            number
+                .parse::<usize>()
+                .with_context(|| format!("Expected a number, found '{number}'"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let &[level, nw, ne, sw, se] = &numbers[..] else {
+        bail!("Expected 'level nw ne sw se', found '{line}'");
+    };
+    let children = [nw, ne, sw, se];
+    if !(1..=MAX_LEVEL).contains(&level) {
+        bail!("Expected a level within 1 and {MAX_LEVEL}, found {level}");
+    }
+    if level == 1 {
+        let states = children
+            .map(|state| u8::try_from(state).map_err(|_| anyhow!("State {state} is above 255")));
+        return Ok(Node::leaf(1, states.into_iter().collect::<Result<_>>()?));
+    }
+    for child in children.into_iter().filter(|&child| child > 0) {
+        let node = nodes
+            .get(child - 1)
+            .ok_or_else(|| anyhow!("Node {child} is not defined before it is used"))?;
+        if node.level != level - 1 {
+            bail!(
+                "Node {child} is of level {}, expected {} below a node of level {level}",
+                node.level,
+                level - 1
+            );
+        }
+    }
+    Ok(Node::branch(level, children, nodes))
+}
+
+/// Expands the quadtree, whose root is centred on cell (0, 0) as in Golly.
+/// Patterns whose populated cells don't fit in `limit` are refused before they are expanded
+pub fn parse(text: &str, limit: Option<[u32; 2]>) -> Result<Pattern> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()));
+    match lines.next() {
+        Some((_, header)) if header.starts_with("[M2]") => {}
+        _ => bail!("Line 1: expected the '[M2]' header"),
+    }
+
+    let mut metadata = Pattern::default();
+    // Node n is at index n - 1, as 0 stands for empty squares
+    let mut nodes = Vec::new();
+    for (number, line) in lines {
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            let mut chars = comment.chars();
+            let kind = chars.next();
+            let content = chars.as_str().trim().to_string();
+            match kind {
+                Some('R') => metadata.rule = Some(content),
+                Some('N') => metadata.name = Some(content),
+                Some('C') => metadata.comments.push(content),
+                // Generation counts and other comments aren't kept
+                _ => {}
+            }
+            continue;
+        }
+        let node = if line.starts_with(['.', '*', '$']) {
+            parse_leaf(line)
+        } else {
+            parse_node(line, &nodes)
+        };
+        nodes.push(node.with_context(|| format!("Line {number}"))?);
+    }
+
+    let mut cells = Vec::new();
+    if let Some(
+        root @ Node {
+            bounds: Some([min, max]),
+            ..
+        },
+    ) = nodes.last()
+    {
+        let size = [max[0] - min[0] + 1, max[1] - min[1] + 1];
+        if let Some(limit) = limit {
+            if size[0] > limit[0] as u64 || size[1] > limit[1] as u64 {
+                bail!(
+                    "The pattern is {}x{} cells, larger than the {}x{} grid",
+                    size[0],
+                    size[1],
+                    limit[0],
+                    limit[1]
+                );
+            }
+        }
+        if root.population > MAX_CELLS {
+            bail!(
+                "The pattern has {} cells, more than the {MAX_CELLS} read at most",
+                root.population
+            );
+        }
+        let corner = -(1i64 << (root.level - 1));
+        let within = |coordinate: u64| i32::try_from(corner + coordinate as i64).is_ok();
+        if !min.iter().chain(max).all(|&coordinate| within(coordinate)) {
+            bail!("The pattern lies beyond the cells of a grid");
+        }
+        expand(&nodes, nodes.len(), [corner; 2], &mut cells);
+    }
+    Ok(Pattern {
+        rule: metadata.rule,
+        name: metadata.name,
+        comments: metadata.comments,
+        ..Pattern::with_cells(cells, None)?
+    })
+}
+
+/// Adds the cells of node `number`, whose top left cell is `corner`
+fn expand(nodes: &[Node], number: usize, corner: [i64; 2], cells: &mut Vec<([i32; 2], u8)>) {
+    let Some(node) = number.checked_sub(1).map(|index| &nodes[index]) else {
+        return;
+    };
+    if node.population == 0 {
+        return;
+    }
+    match &node.kind {
+        NodeKind::Leaf(states) => {
+            let side = 1 << node.level;
+            for (index, &state) in states.iter().enumerate() {
+                if state > 0 {
+                    let cell = [
+                        corner[0] + (index % side) as i64,
+                        corner[1] + (index / side) as i64,
+                    ];
+                    cells.push((cell.map(|coordinate| coordinate as i32), state));
+                }
+            }
+        }
+        NodeKind::Branch(children) => {
+            let half = 1i64 << (node.level - 1);
+            for (quadrant, &child) in children.iter().enumerate() {
+                let corner = [
+                    corner[0] + (quadrant % 2) as i64 * half,
+                    corner[1] + (quadrant / 2) as i64 * half,
+                ];
+                expand(nodes, child, corner, cells);
+            }
+        }
+    }
+}
+
+/// Builds the lines of a quadtree, sharing the nodes of identical squares
+struct Writer {
+    /// Node lines, node n at index n - 1
+    lines: Vec<String>,
+    numbers: HashMap<String, usize>,
+    /// Level of leaves: 8x8 `.` and `*` squares for two states, or 2x2 numbered states
+    leaf_level: usize,
+}
+
+impl Writer {
+    /// Number of the node of the square of `level` at `corner`, or 0 if it has no cells
+    fn node(&mut self, level: usize, corner: [i64; 2], cells: Vec<([i32; 2], u8)>) -> usize {
+        if cells.is_empty() {
+            return 0;
+        }
+        let line = if level == self.leaf_level {
+            let side = 1 << level;
+            let mut states = vec![0; side * side];
+            for ([x, y], state) in cells {
+                let (x, y) = (
+                    (x as i64 - corner[0]) as usize,
+                    (y as i64 - corner[1]) as usize,
+                );
+                states[y * side + x] = state;
+            }
+            match level {
+                1 => format!("1 {} {} {} {}", states[0], states[1], states[2], states[3]),
+                _ => leaf_line(&states, side),
+            }
+        } else {
+            let half = 1i64 << (level - 1);
+            let mut quadrants: [Vec<_>; 4] = Default::default();
+            for (cell @ [x, y], state) in cells {
+                let east = (x as i64 >= corner[0] + half) as usize;
+                let south = (y as i64 >= corner[1] + half) as usize;
+                quadrants[south * 2 + east].push((cell, state));
+            }
+            let mut children = [0; 4];
+            for (quadrant, cells) in quadrants.into_iter().enumerate() {
+                let corner = [
+                    corner[0] + (quadrant % 2) as i64 * half,
+                    corner[1] + (quadrant / 2) as i64 * half,
+                ];
+                children[quadrant] = self.node(level - 1, corner, cells);
+            }
+            let [nw, ne, sw, se] = children;
+            format!("{level} {nw} {ne} {sw} {se}")
+        };
+        if let Some(&number) = self.numbers.get(&line) {
+            return number;
+        }
+        self.lines.push(line.clone());
+        self.numbers.insert(line, self.lines.len());
+        self.lines.len()
+    }
+}
+
+/// Writes the rows of a two state leaf, up to the last alive cell of each
+fn leaf_line(states: &[u8], side: usize) -> String {
+    let rows: Vec<&[u8]> = states.chunks(side).collect();
+    let last_row = rows
+        .iter()
+        .rposition(|row| row.iter().any(|&state| state > 0))
+        .map_or(0, |last| last + 1);
+    let mut line = String::new();
+    for row in &rows[..last_row] {
+        let end = row
+            .iter()
+            .rposition(|&state| state > 0)
+            .map_or(0, |last| last + 1);
+        line.extend(
+            row[..end]
+                .iter()
+                .map(|&state| if state > 0 { '*' } else { '.' }),
+        );
+        line.push('$');
+    }
+    line
+}
+
+/// Writes the cells as they are, in the smallest quadtree centred on cell (0, 0) holding them
+pub fn format(pattern: &Pattern) -> Result<String> {
+    let mut text = String::new();
+    writeln!(text, "[M2] (cells {})", env!("CARGO_PKG_VERSION"))?;
+    if let Some(rule) = &pattern.rule {
+        writeln!(text, "#R {rule}")?;
+    }
+    if let Some(name) = &pattern.name {
+        writeln!(text, "#N {name}")?;
+    }
+    for comment in &pattern.comments {
+        writeln!(text, "#C {comment}")?;
+    }
+
+    // Levels of the root's halves grow until they reach the furthest cell from the centre
+    let leaf_level = if pattern.max_state() > 1 {
+        1
+    } else {
+        LEAF_LEVEL
+    };
+    let reach = pattern
+        .cells
+        .iter()
+        .flat_map(|&(cell, _)| {
+            cell.map(|coordinate| (coordinate as i64 + 1).max(-(coordinate as i64)))
+        })
+        .max()
+        .unwrap_or(0);
+    let mut level = leaf_level;
+    while 1i64 << (level - 1) < reach {
+        level += 1;
+    }
+    let mut writer = Writer {
+        lines: Vec::new(),
+        numbers: HashMap::new(),
+        leaf_level,
+    };
+    writer.node(level, [-(1i64 << (level - 1)); 2], pattern.cells.clone());
+    for line in writer.lines {
+        writeln!(text, "{line}")?;
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GLIDER: &str = "\
+[M2] (golly 2.0)
+#R B3/S23
+#N Glider
+$.*$..*$***$
+4 0 0 0 1
+";
+
+    fn round_trip(pattern: &Pattern) -> Pattern {
+        parse(&format(pattern).unwrap(), None).unwrap()
+    }
+
+    fn error(text: &str, limit: Option<[u32; 2]>) -> String {
+        format!("{:#}", parse(text, limit).unwrap_err())
+    }
+
+    #[test]
+    fn expands_around_the_centre() {
+        let glider = parse(GLIDER, None).unwrap();
+        assert_eq!(glider.rule.as_deref(), Some("B3/S23"));
+        assert_eq!(glider.name.as_deref(), Some("Glider"));
+        assert_eq!((glider.origin, glider.width, glider.height), ([0, 1], 3, 3));
+        assert_eq!(glider.states().unwrap(), [0, 1, 0, 0, 0, 1, 1, 1, 1]);
+        assert_eq!(round_trip(&glider), glider);
+    }
+
+    #[test]
+    fn round_trips_two_and_more_states() {
+        // Cells in every quadrant, sharing the node of a repeated leaf
+        let mut cells: Vec<_> = [[-20, -20], [-20, 5], [5, -20], [5, 5], [-1, -1], [2, 2]]
+            .into_iter()
+            .flat_map(|[x, y]| [([x, y], 1), ([x + 1, y], 1), ([x + 2, y + 1], 1)])
+            .collect();
+        cells.sort_by_key(|&([x, y], _)| (x, y));
+        let pattern = Pattern::with_cells(cells.clone(), None).unwrap();
+        let mut read = round_trip(&pattern);
+        read.cells.sort_by_key(|&([x, y], _)| (x, y));
+        assert_eq!(read, pattern);
+
+        cells
+            .iter_mut()
+            .enumerate()
+            .for_each(|(index, (_, state))| *state = index as u8 + 1);
+        let pattern = Pattern {
+            rule: Some("23/3/64".to_string()),
+            ..Pattern::with_cells(cells, None).unwrap()
+        };
+        let text = format(&pattern).unwrap();
+        assert!(text.lines().any(|line| line.starts_with("1 ")));
+        let mut read = parse(&text, None).unwrap();
+        read.cells.sort_by_key(|&([x, y], _)| (x, y));
+        assert_eq!(read, pattern);
+    }
+
+    #[test]
+    fn refuses_large_patterns_and_undefined_nodes() {
+        assert_eq!(
+            error(GLIDER, Some([2, 8])),
+            "The pattern is 3x3 cells, larger than the 2x8 grid"
+        );
+        assert!(parse(GLIDER, Some([3, 3])).is_ok());
+        assert_eq!(
+            error("[M2]\n$.*$\n3 1 0 0 0\n", None),
+            "Line 3: Node 1 is of level 3, expected 2 below a node of level 3"
+        );
+        assert_eq!(
+            error("[M2]\n4 0 0 0 2\n", None),
+            "Line 2: Node 2 is not defined before it is used"
+        );
+    }
+}
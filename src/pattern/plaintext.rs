@@ -0,0 +1,110 @@
+use super::Pattern;
+use anyhow::{bail, Result};
+use std::fmt::Write;
+
+/// The bounding box is the whole drawing, keeping its blank rows and columns
+pub fn parse(text: &str) -> Result<Pattern> {
+    let mut metadata = Pattern::default();
+    let mut cells = Vec::new();
+    let (mut width, mut y) = (0u32, 0u32);
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        if let Some(comment) = line.strip_prefix('!') {
+            match comment.strip_prefix("Name:") {
+                Some(name) if metadata.name.is_none() => metadata.name = Some(name.trim().into()),
+                _ => metadata.comments.push(comment.trim().into()),
+            }
+            continue;
+        }
+        let row = line.trim_end();
+        for (x, c) in row.chars().enumerate() {
+            match c {
+                '.' => {}
+                // Some files mark alive cells with asterisks
+                'O' | '*' => cells.push(([x as i32, y as i32], 1)),
+                c => bail!("Line {number}: unexpected '{c}', expected '.' or 'O'"),
+            }
+        }
+        width = width.max(row.chars().count() as u32);
+        y += 1;
+    }
+    Ok(Pattern {
+        name: metadata.name,
+        comments: metadata.comments,
+        ..Pattern::with_cells(cells, Some(([0, 0], [width, y])))?
+    })
+}
+
+/// Rows are written as wide as the bounding box, so it survives reading back
+pub fn format(pattern: &Pattern) -> Result<String> {
+    pattern.check_two_states("Plaintext")?;
+    let mut text = String::new();
+    if let Some(name) = &pattern.name {
+        writeln!(text, "!Name: {name}")?;
+    }
+    for comment in &pattern.comments {
+        writeln!(text, "!{comment}")?;
+    }
+    let states = pattern.states()?;
+    if pattern.width > 0 {
+        for row in states.chunks(pattern.width as usize) {
+            let row: String = row
+                .iter()
+                .map(|&state| if state > 0 { 'O' } else { '.' })
+                .collect();
+            writeln!(text, "{row}")?;
+        }
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(pattern: &Pattern) -> Pattern {
+        parse(&format(pattern).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn round_trips_drawings() {
+        let text = "!Name: Glider\n!The smallest spaceship\n.O.\n..O\nOOO\n";
+        let glider = parse(text).unwrap();
+        assert_eq!(glider.name.as_deref(), Some("Glider"));
+        assert_eq!(glider.comments, ["The smallest spaceship"]);
+        assert_eq!((glider.width, glider.height), (3, 3));
+        assert_eq!(glider.states().unwrap(), [0, 1, 0, 0, 0, 1, 1, 1, 1]);
+        assert_eq!(format(&glider).unwrap(), text);
+        assert_eq!(round_trip(&glider), glider);
+    }
+
+    #[test]
+    fn keeps_blank_rows_and_columns() {
+        // Blank leading and trailing rows, and a row shorter than the others
+        let text = "......\n..O\n.O..O.\n......\n";
+        let pattern = parse(text).unwrap();
+        assert_eq!(
+            (pattern.origin, pattern.width, pattern.height),
+            ([0, 0], 6, 4)
+        );
+        assert_eq!(pattern.cells, [([2, 1], 1), ([1, 2], 1), ([4, 2], 1)]);
+        assert_eq!(
+            format(&pattern).unwrap(),
+            "......\n..O...\n.O..O.\n......\n"
+        );
+        assert_eq!(round_trip(&pattern), pattern);
+        let empty = parse("!Nothing\n").unwrap();
+        assert_eq!((empty.width, empty.height), (0, 0));
+        assert_eq!(round_trip(&empty), empty);
+    }
+
+    #[test]
+    fn refuses_other_states() {
+        assert_eq!(
+            format!("{:#}", parse("..\n.x\n").unwrap_err()),
+            "Line 2: unexpected 'x', expected '.' or 'O'"
+        );
+        let pattern = Pattern::with_cells(vec![([0, 0], 2)], None).unwrap();
+        assert!(format(&pattern).is_err());
+    }
+}
@@ -0,0 +1,376 @@
+use super::{Pattern, MAX_CELLS};
+use anyhow::{anyhow, bail, Context, Result};
+use std::fmt::Write;
+
+/// Golly wraps encoded lines at this many characters
+const LINE_LENGTH: usize = 70;
+
+/// Parses `x = 3, y = 3, rule = B3/S23`. The rule comes last and may itself contain commas
+fn parse_header(header: &str) -> Result<(u32, u32, Option<String>)> {
+    let (sizes, rule) = match header.find("rule") {
+        Some(start) => {
+            let (sizes, rule) = header.split_at(start);
+            let rule = rule["rule".len()..]
+                .trim_start()
+                .strip_prefix('=')
+                .ok_or_else(|| anyhow!("Expected '=' after 'rule'"))?;
+            (sizes.trim_end().trim_end_matches(','), Some(rule.trim()))
+        }
+        None => (header, None),
+    };
+    let (mut width, mut height) = (None, None);
+    for assignment in sizes.split(',') {
+        let (key, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Expected 'key = value', found '{}'", assignment.trim()))?;
+        let value = value
+            .trim()
+            .parse::<u32>()
+            .with_context(|| format!("Expected a size, found '{}'", value.trim()))?;
+        match key.trim() {
+            "x" => width = Some(value),
+            "y" => height = Some(value),
+            key => bail!("Unexpected header key '{key}'"),
+        }
+    }
+    Ok((
+        width.ok_or_else(|| anyhow!("Missing width 'x'"))?,
+        height.ok_or_else(|| anyhow!("Missing height 'y'"))?,
+        rule.filter(|rule| !rule.is_empty()).map(str::to_string),
+    ))
+}
+
+/// Cells beyond the size in the header grow the pattern, as in Golly
+pub fn parse(text: &str) -> Result<Pattern> {
+    let mut metadata = Pattern::default();
+    let mut header = None;
+    // Top left cell, from `#P` or `#R` lines
+    let mut origin = [0, 0];
+    let mut cells = Vec::new();
+    let (mut x, mut y) = (0u32, 0u32);
+    let mut ended = false;
+
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let line = line.trim();
+        if ended {
+            break;
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            let mut chars = comment.chars();
+            let kind = chars.next();
+            let content = chars.as_str().trim().to_string();
+            match kind {
+                Some('N') => metadata.name = Some(content),
+                Some('C' | 'c') => metadata.comments.push(content),
+                Some('r') if metadata.rule.is_none() => metadata.rule = Some(content),
+                Some('P' | 'R') => {
+                    origin = parse_position(&content)
+                        .with_context(|| format!("Line {number}: invalid position"))?
+                }
+                // Authors and other comments aren't kept
+                _ => {}
+            }
+            continue;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        if header.is_none() {
+            let (width, height, rule) =
+                parse_header(line).with_context(|| format!("Line {number}: invalid header"))?;
+            header = Some((width, height));
+            metadata.rule = rule.or(metadata.rule.take());
+            continue;
+        }
+
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            let mut count = None;
+            let mut c = c;
+            if let Some(digit) = c.to_digit(10) {
+                let mut run = digit;
+                while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                    chars.next();
+                    run = run
+                        .checked_mul(10)
+                        .and_then(|run| run.checked_add(digit))
+                        .ok_or_else(|| anyhow!("Line {number}: run count is too large"))?;
+                }
+                count = Some(run);
+                c = chars
+                    .next()
+                    .ok_or_else(|| anyhow!("Line {number}: expected a state after {run}"))?;
+            }
+            let count = count.unwrap_or(1);
+            let state = match c {
+                '!' => {
+                    ended = true;
+                    break;
+                }
+                '$' => {
+                    y = y
+                        .checked_add(count)
+                        .ok_or_else(|| anyhow!("Line {number}: pattern is too high"))?;
+                    x = 0;
+                    continue;
+                }
+                c if c.is_whitespace() => continue,
+                'b' | '.' => 0,
+                'A'..='X' => c as u8 - b'A' + 1,
+                'p'..='y' if chars.peek().is_some_and(|c| matches!(c, 'A'..='X')) => {
+                    let letter = chars.next().unwrap();
+                    let state = 25 + (c as u32 - 'p' as u32) * 24 + (letter as u32 - 'A' as u32);
+                    u8::try_from(state)
+                        .map_err(|_| anyhow!("Line {number}: state '{c}{letter}' is above 255"))?
+                }
+                // Two state patterns may mark alive cells with any other letter
+                c if c.is_ascii_lowercase() => 1,
+                c => bail!("Line {number}: unexpected '{c}'"),
+            };
+            let end = x
+                .checked_add(count)
+                .filter(|&end| i64::from(origin[0]) + i64::from(end) <= i64::from(i32::MAX))
+                .ok_or_else(|| anyhow!("Line {number}: pattern is too wide"))?;
+            if state > 0 {
+                if cells.len() as u64 + u64::from(count) > MAX_CELLS {
+                    bail!("Line {number}: the pattern has more than the {MAX_CELLS} cells read at most");
+                }
+                if i64::from(origin[1]) + i64::from(y) > i64::from(i32::MAX) {
+                    bail!("Line {number}: pattern is too high");
+                }
+                cells.extend(
+                    (x..end).map(|x| ([origin[0] + x as i32, origin[1] + y as i32], state)),
+                );
+            }
+            x = end;
+        }
+    }
+
+    let (width, height) = header.ok_or_else(|| anyhow!("Missing 'x = , y = ' header"))?;
+    Ok(Pattern {
+        rule: metadata.rule,
+        name: metadata.name,
+        comments: metadata.comments,
+        ..Pattern::with_cells(cells, Some((origin, [width, height])))?
+    })
+}
+
+/// Parses the `x y` of a position line
+fn parse_position(position: &str) -> Result<[i32; 2]> {
+    let mut coordinates = position.split_whitespace().map(|coordinate| {
+        coordinate
+            .parse::<i32>()
+            .with_context(|| format!("Expected a coordinate, found '{coordinate}'"))
+    });
+    match (coordinates.next(), coordinates.next(), coordinates.next()) {
+        (Some(x), Some(y), None) => Ok([x?, y?]),
+        _ => bail!("Expected 'x y', found '{position}'"),
+    }
+}
+
+pub fn format(pattern: &Pattern) -> Result<String> {
+    let mut text = String::new();
+    if let Some(name) = &pattern.name {
+        writeln!(text, "#N {name}")?;
+    }
+    for comment in &pattern.comments {
+        writeln!(text, "#C {comment}")?;
+    }
+    write!(text, "x = {}, y = {}", pattern.width, pattern.height)?;
+    if let Some(rule) = &pattern.rule {
+        write!(text, ", rule = {rule}")?;
+    }
+    writeln!(text)?;
+
+    let multistate = pattern.max_state() > 1;
+    let letters = |state: u8| match (state, multistate) {
+        (0, false) => "b".to_string(),
+        (_, false) => "o".to_string(),
+        (0, true) => ".".to_string(),
+        (1..=24, true) => ((b'A' + state - 1) as char).to_string(),
+        (_, true) => {
+            let index = state - 25;
+            format!(
+                "{}{}",
+                (b'p' + index / 24) as char,
+                (b'A' + index % 24) as char
+            )
+        }
+    };
+
+    // Runs of (count, symbol), without the dead cells ending rows and the empty rows
+    // ending the pattern, and with consecutive row ends merged
+    let mut runs: Vec<(u32, String)> = Vec::new();
+    let mut push = |count: u32, symbol: String| match runs.last_mut() {
+        Some((last_count, last)) if *last == symbol => *last_count += count,
+        _ => runs.push((count, symbol)),
+    };
+    let states = pattern.states()?;
+    let rows: Vec<&[u8]> = match pattern.width {
+        0 => Vec::new(),
+        width => states.chunks(width as usize).collect(),
+    };
+    let last_row = rows
+        .iter()
+        .rposition(|row| row.iter().any(|&state| state > 0));
+    for (y, row) in rows
+        .iter()
+        .take(last_row.map_or(0, |last| last + 1))
+        .enumerate()
+    {
+        if y > 0 {
+            push(1, "$".to_string());
+        }
+        let end = row
+            .iter()
+            .rposition(|&state| state > 0)
+            .map_or(0, |last| last + 1);
+        let mut x = 0;
+        while x < end {
+            let state = row[x];
+            let run = row[x..end]
+                .iter()
+                .take_while(|&&other| other == state)
+                .count();
+            push(run as u32, letters(state));
+            x += run;
+        }
+    }
+    push(1, "!".to_string());
+
+    let mut line = String::new();
+    for (count, symbol) in runs {
+        let run = match count {
+            1 => symbol,
+            _ => format!("{count}{symbol}"),
+        };
+        if line.len() + run.len() > LINE_LENGTH {
+            writeln!(text, "{line}")?;
+            line.clear();
+        }
+        line += &run;
+    }
+    writeln!(text, "{line}")?;
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::Region;
+
+    const GLIDER: &str = "\
+#N Glider
+#C The smallest spaceship
+#C Found by Richard Guy in 1969
+x = 3, y = 3, rule = B3/S23
+bo$2bo$3o!
+";
+
+    fn round_trip(pattern: &Pattern) -> Pattern {
+        parse(&format(pattern).unwrap()).unwrap()
+    }
+
+    fn error(text: &str) -> String {
+        format!("{:#}", parse(text).unwrap_err())
+    }
+
+    #[test]
+    fn parses_header_and_comments() {
+        let glider = parse(GLIDER).unwrap();
+        assert_eq!(glider.name.as_deref(), Some("Glider"));
+        assert_eq!(
+            glider.comments,
+            ["The smallest spaceship", "Found by Richard Guy in 1969"]
+        );
+        assert_eq!(glider.rule.as_deref(), Some("B3/S23"));
+        assert_eq!((glider.width, glider.height), (3, 3));
+        assert_eq!(glider.states().unwrap(), [0, 1, 0, 0, 0, 1, 1, 1, 1]);
+        assert_eq!(format(&glider).unwrap(), GLIDER);
+        assert_eq!(round_trip(&glider), glider);
+    }
+
+    #[test]
+    fn round_trips_multistate_letters() {
+        // States 1 to 24 are single letters, and 25 to 255 from pA to yO
+        let cells = (1..=u8::MAX)
+            .map(|state| ([i32::from(state) % 16, i32::from(state) / 16], state))
+            .collect();
+        let pattern = Pattern {
+            rule: Some("23/3/256".to_string()),
+            ..Pattern::with_cells(cells, None).unwrap()
+        };
+        let text = format(&pattern).unwrap();
+        assert!(text.contains("pA") && text.contains("yO"));
+        assert_eq!(round_trip(&pattern), pattern);
+        assert_eq!(
+            parse("x = 2, y = 1\nXpA!").unwrap().states().unwrap(),
+            [24, 25]
+        );
+        assert_eq!(
+            error("x = 1, y = 1\nyP!"),
+            "Line 2: state 'yP' is above 255"
+        );
+    }
+
+    #[test]
+    fn wraps_lines() {
+        // Alternating runs of growing lengths, far wider than a line
+        let mut cells: Vec<_> = (0..200)
+            .filter(|x| (x / 3) % 2 == 0)
+            .map(|x| ([x, x % 5], 1 + (x % 3) as u8))
+            .collect();
+        // Parsing lists cells in row-major order
+        cells.sort_by_key(|&([x, y], _)| (y, x));
+        let pattern = Pattern::with_cells(cells, None).unwrap();
+        let text = format(&pattern).unwrap();
+        let body: Vec<&str> = text.lines().skip(1).collect();
+        assert!(body.len() > 1);
+        assert!(body.iter().all(|line| line.len() <= LINE_LENGTH));
+        assert_eq!(round_trip(&pattern), pattern);
+    }
+
+    #[test]
+    fn round_trips_crops() {
+        let glider = parse(GLIDER).unwrap();
+        let crop = glider
+            .crop(Region {
+                x: 1,
+                y: 1,
+                width: 2,
+                height: 2,
+            })
+            .unwrap();
+        assert_eq!(crop.states().unwrap(), [0, 1, 1, 1]);
+        // Files don't hold the origin, so crops are read back from (0, 0)
+        assert_eq!(
+            round_trip(&crop),
+            Pattern {
+                cells: vec![([1, 0], 1), ([0, 1], 1), ([1, 1], 1)],
+                origin: [0, 0],
+                ..crop
+            }
+        );
+    }
+
+    #[test]
+    fn refuses_huge_runs() {
+        assert_eq!(
+            error("x = 3, y = 1\n4294967295o!"),
+            "Line 2: pattern is too wide"
+        );
+        assert_eq!(
+            error("x = 3, y = 1\n2147483647o!"),
+            "Line 2: the pattern has more than the 67108864 cells read at most"
+        );
+        assert_eq!(
+            error("x = 3, y = 1\n4294967295b4294967295o!"),
+            "Line 2: pattern is too wide"
+        );
+        assert_eq!(
+            error("x = 3, y = 1\n42949672950o!"),
+            "Line 2: run count is too large"
+        );
+    }
+}
@@ -1,2 +1,6 @@
+pub mod brush;
+pub mod camera;
+pub mod colormap;
+pub mod playback;
 pub mod renderer;
 pub mod window;
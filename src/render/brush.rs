@@ -0,0 +1,190 @@
+use anyhow::bail;
+use std::str::FromStr;
+
+/// Brush radii, in cells
+pub const DEFAULT_RADIUS: u32 = 2;
+const MAX_RADIUS: u32 = 256;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum BrushShape {
+    Square,
+    #[default]
+    Circle,
+}
+
+/// Cells painted around the cursor: a single cell at radius 0,
+/// otherwise every cell within `radius` of it, in the shape's metric
+#[derive(Copy, Clone, Debug)]
+pub struct Brush {
+    pub radius: u32,
+    pub shape: BrushShape,
+}
+
+impl Brush {
+    pub fn new(radius: u32, shape: BrushShape) -> anyhow::Result<Self> {
+        if radius > MAX_RADIUS {
+            bail!("Brush radius must be at most {MAX_RADIUS} cells, found {radius}");
+        }
+        Ok(Self { radius, shape })
+    }
+
+    pub fn grow(&mut self) {
+        self.radius = (self.radius + 1).min(MAX_RADIUS);
+    }
+
+    pub fn shrink(&mut self) {
+        self.radius = self.radius.saturating_sub(1);
+    }
+
+    pub fn toggle_shape(&mut self) {
+        self.shape = match self.shape {
+            BrushShape::Square => BrushShape::Circle,
+            BrushShape::Circle => BrushShape::Square,
+        };
+    }
+
+    /// Rows of cells covered when centered on a cell, as `(y, first x, last x)`.
+    /// They may lie beyond the edges of the grid
+    pub fn spans(&self, center: [i32; 2]) -> impl Iterator<Item = (i32, i32, i32)> {
+        let radius = self.radius as i32;
+        let shape = self.shape;
+        (-radius..=radius).map(move |dy| {
+            let extent = match shape {
+                BrushShape::Square => radius,
+                // Widest row that stays within the circle, rounded so small brushes are not squares
+                BrushShape::Circle => ((radius * radius + radius - dy * dy) as f32).sqrt() as i32,
+            };
+            (center[1] + dy, center[0] - extent, center[0] + extent)
+        })
+    }
+
+    /// Cells to center the brush on along a drag from one cell to another, excluding the start,
+    /// close enough together that the stamps leave no gaps
+    pub fn stroke(&self, from: [i32; 2], to: [i32; 2]) -> impl Iterator<Item = [i32; 2]> {
+        let delta = [to[0] - from[0], to[1] - from[1]];
+        let stride = self.radius.max(1) as i32;
+        let steps = (delta[0].abs().max(delta[1].abs()) + stride - 1) / stride;
+        (1..=steps).map(move |step| {
+            let t = step as f32 / steps as f32;
+            [
+                from[0] + (delta[0] as f32 * t).round() as i32,
+                from[1] + (delta[1] as f32 * t).round() as i32,
+            ]
+        })
+    }
+}
+
+impl FromStr for BrushShape {
+    type Err = anyhow::Error;
+
+    fn from_str(shape: &str) -> anyhow::Result<Self> {
+        Ok(match shape.to_ascii_lowercase().as_str() {
+            "square" => Self::Square,
+            "circle" | "round" => Self::Circle,
+            _ => bail!("Unknown brush shape '{shape}', expected square or circle"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// Ends of drags in every direction, steep and shallow, from the origin
+    const DRAG_ENDS: [[i32; 2]; 8] = [
+        [17, 0],
+        [0, -13],
+        [9, 9],
+        [-23, 7],
+        [5, -31],
+        [-12, -29],
+        [40, 3],
+        [1, 1],
+    ];
+
+    fn cells(brush: &Brush, center: [i32; 2]) -> Vec<[i32; 2]> {
+        brush
+            .spans(center)
+            .flat_map(|(y, first, last)| (first..=last).map(move |x| [x, y]))
+            .collect()
+    }
+
+    #[test]
+    fn spans_cover_the_shape() {
+        let row_widths = |brush: Brush| -> Vec<i32> {
+            brush
+                .spans([10, -4])
+                .map(|(_, first, last)| last - first + 1)
+                .collect()
+        };
+        for shape in [BrushShape::Square, BrushShape::Circle] {
+            assert_eq!(
+                Brush::new(0, shape)
+                    .unwrap()
+                    .spans([10, -4])
+                    .collect::<Vec<_>>(),
+                [(-4, 10, 10)]
+            );
+        }
+        assert_eq!(
+            row_widths(Brush::new(2, BrushShape::Square).unwrap()),
+            [5; 5]
+        );
+        assert_eq!(
+            row_widths(Brush::new(2, BrushShape::Circle).unwrap()),
+            [3, 5, 5, 5, 3]
+        );
+        let rows: Vec<i32> = Brush::new(3, BrushShape::Circle)
+            .unwrap()
+            .spans([10, -4])
+            .map(|(y, ..)| y)
+            .collect();
+        assert_eq!(rows, (-7..=-1).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn single_cell_strokes_are_connected() {
+        let brush = Brush::new(0, BrushShape::Circle).unwrap();
+        for to in DRAG_ENDS {
+            let centers: Vec<[i32; 2]> = brush.stroke([0, 0], to).collect();
+            assert_eq!(centers.last(), Some(&to));
+            let mut previous = [0, 0];
+            for center in centers {
+                let gap = (center[0] - previous[0])
+                    .abs()
+                    .max((center[1] - previous[1]).abs());
+                assert_eq!(gap, 1, "{previous:?} to {center:?} towards {to:?}");
+                previous = center;
+            }
+        }
+    }
+
+    #[test]
+    fn wide_strokes_cover_the_drag() {
+        for shape in [BrushShape::Square, BrushShape::Circle] {
+            for radius in [1, 2, 5] {
+                let brush = Brush::new(radius, shape).unwrap();
+                for to in DRAG_ENDS {
+                    let mut painted: HashSet<[i32; 2]> =
+                        cells(&brush, [0, 0]).into_iter().collect();
+                    for center in brush.stroke([0, 0], to) {
+                        painted.extend(cells(&brush, center));
+                    }
+                    // Cells the cursor passed over on its way
+                    for sample in 0..=100 {
+                        let t = sample as f32 / 100.0;
+                        let cell = [
+                            (to[0] as f32 * t).round() as i32,
+                            (to[1] as f32 * t).round() as i32,
+                        ];
+                        assert!(
+                            painted.contains(&cell),
+                            "{shape:?} radius {radius} towards {to:?} misses {cell:?}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+/// Zoom limits, as magnification over the grid fitted to the window
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 4096.0;
+
+/// Magnification per line scrolled
+const ZOOM_PER_LINE: f32 = 1.25;
+
+/// Pan and zoom of the grid, on top of scaling the whole grid to fit the window
+pub struct Camera {
+    zoom: f32,
+    /// Translation of the grid in normalized device coordinates
+    offset: [f32; 2],
+    grid_size: PhysicalSize<u32>,
+    window_size: PhysicalSize<u32>,
+}
+
+/// Laid out to match `Camera` in `render.wgsl`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    /// Size of the grid in normalized device coordinates, halved
+    scale: [f32; 2],
+    offset: [f32; 2],
+}
+
+impl Camera {
+    pub fn new(grid_size: PhysicalSize<u32>, window_size: PhysicalSize<u32>) -> Self {
+        Self {
+            zoom: 1.0,
+            offset: [0.0; 2],
+            grid_size,
+            window_size,
+        }
+    }
+
+    /// Shows the whole grid again, as large as fits the window
+    pub fn fit(&mut self) {
+        self.zoom = 1.0;
+        self.offset = [0.0; 2];
+    }
+
+    pub fn resize(&mut self, window_size: PhysicalSize<u32>) {
+        self.window_size = window_size;
+    }
+
+    /// Zooms by the lines scrolled, keeping the point under the cursor in place
+    pub fn zoom_at(&mut self, cursor: PhysicalPosition<f64>, lines: f32) {
+        let zoom = (self.zoom * ZOOM_PER_LINE.powf(lines)).clamp(MIN_ZOOM, MAX_ZOOM);
+        let factor = zoom / self.zoom;
+        let cursor = self.to_ndc(cursor);
+        for (offset, cursor) in self.offset.iter_mut().zip(cursor) {
+            *offset = cursor - (cursor - *offset) * factor;
+        }
+        self.zoom = zoom;
+    }
+
+    /// Moves the grid along with the cursor, by a distance in pixels
+    pub fn pan(&mut self, delta: PhysicalPosition<f64>) {
+        self.offset[0] += 2.0 * delta.x as f32 / self.window_size.width.max(1) as f32;
+        self.offset[1] -= 2.0 * delta.y as f32 / self.window_size.height.max(1) as f32;
+    }
+
+    /// Cell under a position in the window, which may lie beyond the edges of the grid
+    pub fn window_to_grid(&self, position: PhysicalPosition<f64>) -> [f32; 2] {
+        let ndc = self.to_ndc(position);
+        let scale = self.uniform().scale;
+        // The grid's left edge is at -scale and its top at +scale, with rows going down
+        [
+            (ndc[0] - self.offset[0] + scale[0]) / (2.0 * scale[0]) * self.grid_size.width as f32,
+            (scale[1] - ndc[1] + self.offset[1]) / (2.0 * scale[1]) * self.grid_size.height as f32,
+        ]
+    }
+
+    pub fn uniform(&self) -> CameraUniform {
+        // Largest size that shows the whole grid with square cells
+        let grid_aspect = self.grid_size.width as f32 / self.grid_size.height as f32;
+        let window_aspect =
+            self.window_size.width.max(1) as f32 / self.window_size.height.max(1) as f32;
+        CameraUniform {
+            scale: [
+                (grid_aspect / window_aspect).min(1.0) * self.zoom,
+                (window_aspect / grid_aspect).min(1.0) * self.zoom,
+            ],
+            offset: self.offset,
+        }
+    }
+
+    fn to_ndc(&self, position: PhysicalPosition<f64>) -> [f32; 2] {
+        [
+            2.0 * position.x as f32 / self.window_size.width.max(1) as f32 - 1.0,
+            1.0 - 2.0 * position.y as f32 / self.window_size.height.max(1) as f32,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRID: PhysicalSize<u32> = PhysicalSize::new(100, 50);
+
+    fn assert_near(cell: [f32; 2], expected: [f32; 2]) {
+        assert!(
+            (cell[0] - expected[0]).abs() < 1e-3 && (cell[1] - expected[1]).abs() < 1e-3,
+            "{cell:?}, expected {expected:?}"
+        );
+    }
+
+    #[test]
+    fn maps_centre_and_corners_to_cells() {
+        // Windows wider and taller than the grid leave margins beside and above it
+        for (window, top_left, bottom_right) in [
+            (PhysicalSize::new(200, 100), [0.0, 0.0], [100.0, 50.0]),
+            (PhysicalSize::new(400, 100), [-50.0, 0.0], [150.0, 50.0]),
+            (PhysicalSize::new(200, 200), [0.0, -25.0], [100.0, 75.0]),
+        ] {
+            let camera = Camera::new(GRID, window);
+            let (width, height) = (window.width as f64, window.height as f64);
+            let at = |x, y| camera.window_to_grid(PhysicalPosition::new(x, y));
+            assert_near(at(width / 2.0, height / 2.0), [50.0, 25.0]);
+            assert_near(at(0.0, 0.0), top_left);
+            assert_near(at(width, height), bottom_right);
+            assert_near(at(width, 0.0), [bottom_right[0], top_left[1]]);
+            assert_near(at(0.0, height), [top_left[0], bottom_right[1]]);
+        }
+    }
+
+    #[test]
+    fn zooming_keeps_the_cell_under_the_cursor() {
+        for window in [PhysicalSize::new(400, 100), PhysicalSize::new(200, 200)] {
+            let mut camera = Camera::new(GRID, window);
+            for (x, y, lines) in [(30.0, 70.0, 3.0), (180.0, 10.0, -2.0), (5.0, 95.0, 40.0)] {
+                let cursor = PhysicalPosition::new(x, y);
+                let cell = camera.window_to_grid(cursor);
+                camera.zoom_at(cursor, lines);
+                assert_near(camera.window_to_grid(cursor), cell);
+            }
+            // Zooming is clamped, and still keeps the cell in place at the limit
+            assert_eq!(camera.zoom, MAX_ZOOM);
+        }
+    }
+
+    #[test]
+    fn panning_moves_the_grid_with_the_cursor() {
+        for window in [PhysicalSize::new(400, 100), PhysicalSize::new(200, 200)] {
+            let mut camera = Camera::new(GRID, window);
+            camera.zoom_at(PhysicalPosition::new(60.0, 40.0), 5.0);
+            let from = PhysicalPosition::new(120.0, 30.0);
+            let cell = camera.window_to_grid(from);
+            camera.pan(PhysicalPosition::new(-35.0, 22.5));
+            assert_near(
+                camera.window_to_grid(PhysicalPosition::new(85.0, 52.5)),
+                cell,
+            );
+            camera.fit();
+            assert_near(
+                camera.window_to_grid(PhysicalPosition::new(
+                    window.width as f64 / 2.0,
+                    window.height as f64 / 2.0,
+                )),
+                [50.0, 25.0],
+            );
+        }
+    }
+}
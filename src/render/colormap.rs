@@ -0,0 +1,46 @@
+use anyhow::bail;
+use std::str::FromStr;
+
+/// Colors of continuous states and of the dying states of Generations rules,
+/// numbered as the `COLORMAP_` constants in `render.wgsl`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Colormap {
+    #[default]
+    Viridis,
+    Inferno,
+    Turbo,
+    Grayscale,
+}
+
+/// `Colormap` in `render.wgsl`, padded to the 16 bytes uniform buffers are bound in
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ColormapUniform {
+    colormap: u32,
+    _padding: [u32; 3],
+}
+
+impl Colormap {
+    pub fn uniform(&self) -> ColormapUniform {
+        ColormapUniform {
+            colormap: *self as u32,
+            _padding: [0; 3],
+        }
+    }
+}
+
+impl FromStr for Colormap {
+    type Err = anyhow::Error;
+
+    fn from_str(colormap: &str) -> anyhow::Result<Self> {
+        Ok(match colormap.to_ascii_lowercase().as_str() {
+            "viridis" => Self::Viridis,
+            "inferno" => Self::Inferno,
+            "turbo" => Self::Turbo,
+            "grayscale" | "greyscale" | "gray" | "grey" => Self::Grayscale,
+            _ => bail!(
+                "Unknown colormap '{colormap}', expected viridis, inferno, turbo or grayscale"
+            ),
+        })
+    }
+}
@@ -0,0 +1,176 @@
+use std::time::Duration;
+
+/// Limits of the simulation speed, in generations per tick
+const MIN_SPEED: f32 = 1.0 / 64.0;
+const MAX_SPEED: f32 = 64.0;
+
+/// Simulation ticks per second, whatever the display's refresh rate
+pub const DEFAULT_TICK_RATE: u32 = 60;
+
+/// Ticks caught up at most in one frame, so a slow frame doesn't make the next one slower
+const MAX_TICKS_PER_FRAME: u32 = 8;
+
+/// Generations stepped at once when stepping several
+pub const DEFAULT_BATCH: u32 = 10;
+/// Most generations stepped at once, all encoded into a single frame
+const MAX_BATCH: u32 = 1 << 12;
+
+/// How many generations to advance each frame: a steady rate of fixed time ticks while running,
+/// or only the steps asked for while paused
+pub struct Playback {
+    paused: bool,
+    /// Generations per tick, below one to only advance every few ticks
+    speed: f32,
+    tick: Duration,
+    /// Time not yet simulated, less than a tick after every frame
+    lag: Duration,
+    /// Fraction of a generation carried over to the next tick
+    progress: f32,
+    /// Steps asked for but not yet taken
+    pending: u32,
+    /// Generations stepped at once when stepping several
+    batch: u32,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self {
+            paused: false,
+            speed: 1.0,
+            tick: Duration::from_secs(1) / DEFAULT_TICK_RATE,
+            lag: Duration::ZERO,
+            progress: 0.0,
+            pending: 0,
+            batch: DEFAULT_BATCH,
+        }
+    }
+}
+
+impl Playback {
+    pub fn new(tick_rate: u32, batch: u32) -> anyhow::Result<Self> {
+        if tick_rate == 0 {
+            anyhow::bail!("Tick rate must be at least one tick per second");
+        }
+        if !(1..=MAX_BATCH).contains(&batch) {
+            anyhow::bail!("Step batch must be from 1 to {MAX_BATCH} generations, found {batch}");
+        }
+        Ok(Self {
+            tick: Duration::from_secs(1) / tick_rate,
+            batch,
+            ..Self::default()
+        })
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.lag = Duration::ZERO;
+        self.progress = 0.0;
+    }
+
+    /// Pauses and advances the given number of generations on the next frame
+    pub fn step(&mut self, generations: u32) {
+        self.paused = true;
+        self.pending = self.pending.saturating_add(generations);
+    }
+
+    /// Pauses and advances a batch of generations on the next frame
+    pub fn step_batch(&mut self) {
+        self.step(self.batch);
+    }
+
+    pub fn grow_batch(&mut self) {
+        self.batch = (self.batch * 2).min(MAX_BATCH);
+    }
+
+    pub fn shrink_batch(&mut self) {
+        self.batch = (self.batch / 2).max(1);
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed * 2.0).min(MAX_SPEED);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = (self.speed / 2.0).max(MIN_SPEED);
+    }
+
+    /// Generations to advance in a frame, after the time elapsed since the previous one
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        let mut generations = std::mem::take(&mut self.pending);
+        if !self.paused {
+            self.lag = (self.lag + elapsed).min(self.tick * MAX_TICKS_PER_FRAME);
+            let ticks = (self.lag.as_nanos() / self.tick.as_nanos()) as u32;
+            self.lag -= self.tick * ticks;
+            self.progress += self.speed * ticks as f32;
+            let whole = self.progress.floor();
+            self.progress -= whole;
+            generations += whole as u32;
+        }
+        generations
+    }
+
+    /// Window title showing the generation, the speed and the generations of a batch
+    pub fn title(&self, name: &str, generation: usize) -> String {
+        let speed = if self.paused {
+            "paused".to_owned()
+        } else if self.speed >= 1.0 {
+            format!("{} gen/tick", self.speed)
+        } else {
+            format!("1/{} gen/tick", 1.0 / self.speed)
+        };
+        format!(
+            "{name} - generation {generation} - {speed} - steps of {}",
+            self.batch
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick() -> Duration {
+        Duration::from_secs(1) / DEFAULT_TICK_RATE
+    }
+
+    #[test]
+    fn quarter_speed_steps_every_fourth_tick() {
+        let mut playback = Playback::default();
+        playback.slower();
+        playback.slower();
+        let generations: Vec<u32> = (0..8).map(|_| playback.advance(tick())).collect();
+        assert_eq!(generations, [0, 0, 0, 1, 0, 0, 0, 1]);
+        // Half ticks add up to whole ones
+        let generations: Vec<u32> = (0..8).map(|_| playback.advance(tick() / 2)).collect();
+        assert_eq!(generations, [0, 0, 0, 0, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn slow_frames_catch_up_a_bounded_number_of_ticks() {
+        let mut playback = Playback::default();
+        assert_eq!(playback.advance(tick() * 3), 3);
+        assert_eq!(playback.advance(tick() * 100), MAX_TICKS_PER_FRAME);
+        // The lag beyond the cap is dropped rather than carried over
+        assert_eq!(playback.advance(Duration::ZERO), 0);
+        playback.faster();
+        assert_eq!(
+            playback.advance(Duration::from_secs(5)),
+            2 * MAX_TICKS_PER_FRAME
+        );
+    }
+
+    #[test]
+    fn paused_steps_are_taken_once() {
+        let mut playback = Playback::default();
+        playback.step(3);
+        assert_eq!(playback.advance(tick() * 5), 3);
+        assert_eq!(playback.advance(tick() * 5), 0);
+        playback.step_batch();
+        playback.step(1);
+        assert_eq!(playback.advance(Duration::ZERO), DEFAULT_BATCH + 1);
+        assert_eq!(playback.advance(tick()), 0);
+        // Resuming doesn't replay the time spent paused
+        playback.toggle_pause();
+        assert_eq!(playback.advance(tick()), 1);
+    }
+}
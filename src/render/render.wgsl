@@ -1,8 +1,29 @@
-struct SimulationParams {
-    width: u32,
-    height: u32,
+@group(0) @binding(0)
+var cells: texture_2d<f32>;
+@group(0) @binding(1)
+var<uniform> params: SimulationParams;
+
+struct Camera {
+    // Half the size of the grid in normalized device coordinates, after fitting and zooming
+    scale: vec2<f32>,
+    offset: vec2<f32>,
+}
+
+@group(0) @binding(2)
+var<uniform> camera: Camera;
+
+const COLORMAP_VIRIDIS: u32 = 0u;
+const COLORMAP_INFERNO: u32 = 1u;
+const COLORMAP_TURBO: u32 = 2u;
+const COLORMAP_GRAYSCALE: u32 = 3u;
+
+struct Colormap {
+    colormap: u32,
 }
 
+@group(0) @binding(3)
+var<uniform> colormap: Colormap;
+
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
     @location(0) uv_coord: vec2<f32>,
@@ -14,14 +35,107 @@ fn vs_main(
     @location(1) uv_coord: vec2<f32>,
 ) -> VertexOutput {
     var out: VertexOutput;
-    out.position = position;
+    out.position = vec4<f32>(position.xy * camera.scale + camera.offset, position.zw);
     out.uv_coord = uv_coord;
     return out;
 }
 
+// Polynomial fit of matplotlib's viridis colormap, in sRGB
+fn viridis(t: f32) -> vec3<f32> {
+    let c0 = vec3<f32>(0.2777273272234177, 0.005407344544966578, 0.3340998053353061);
+    let c1 = vec3<f32>(0.1050930431085774, 1.404613529898575, 1.384590162594685);
+    let c2 = vec3<f32>(-0.3308618287255563, 0.214847559468213, 0.09509516302823659);
+    let c3 = vec3<f32>(-4.634230498983486, -5.799100973351585, -19.33244095627987);
+    let c4 = vec3<f32>(6.228269936347081, 14.17993336680509, 56.69055260068105);
+    let c5 = vec3<f32>(4.776384997670288, -13.74514537774601, -65.35303263337234);
+    let c6 = vec3<f32>(-5.435455855934631, 4.645852612178535, 26.3124352495832);
+    let x = clamp(t, 0.0, 1.0);
+    return c0 + x * (c1 + x * (c2 + x * (c3 + x * (c4 + x * (c5 + x * c6)))));
+}
+
+// Polynomial fit of matplotlib's inferno colormap, in sRGB
+fn inferno(t: f32) -> vec3<f32> {
+    let c0 = vec3<f32>(0.0002189403691192265, 0.001651004631001012, -0.01948089843709184);
+    let c1 = vec3<f32>(0.1065134194856116, 0.5639564367884091, 3.932712388889277);
+    let c2 = vec3<f32>(11.60249308247187, -3.972853965665698, -15.9423941062914);
+    let c3 = vec3<f32>(-41.70399613139459, 17.43639888205313, 44.35414519872813);
+    let c4 = vec3<f32>(77.162935699427, -33.40235894210092, -81.80730925738993);
+    let c5 = vec3<f32>(-71.31942824499214, 32.62606426397723, 73.20951985803202);
+    let c6 = vec3<f32>(25.13112622477341, -12.24266895238567, -23.07032500287172);
+    let x = clamp(t, 0.0, 1.0);
+    return c0 + x * (c1 + x * (c2 + x * (c3 + x * (c4 + x * (c5 + x * c6)))));
+}
+
+// Polynomial approximation of Google's Turbo colormap, in sRGB
+fn turbo(t: f32) -> vec3<f32> {
+    let red4 = vec4<f32>(0.13572138, 4.61539260, -42.66032258, 132.13108234);
+    let green4 = vec4<f32>(0.09140261, 2.19418839, 4.84296658, -14.18503333);
+    let blue4 = vec4<f32>(0.10667330, 12.64194608, -60.58204836, 110.36276771);
+    let red2 = vec2<f32>(-152.94239396, 59.28637943);
+    let green2 = vec2<f32>(4.27729857, 2.82956604);
+    let blue2 = vec2<f32>(-89.90310912, 27.34824973);
+    let x = clamp(t, 0.0, 1.0);
+    let v4 = vec4<f32>(1.0, x, x * x, x * x * x);
+    let v2 = v4.zw * v4.z;
+    return clamp(vec3<f32>(
+        dot(v4, red4) + dot(v2, red2),
+        dot(v4, green4) + dot(v2, green2),
+        dot(v4, blue4) + dot(v2, blue2),
+    ), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// The surface format is sRGB, which expects linear colors
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let low = color / 12.92;
+    let high = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, color <= vec3<f32>(0.04045));
+}
+
+// Linear color of t in [0, 1] along the selected colormap
+fn map_color(t: f32) -> vec3<f32> {
+    var color = vec3<f32>(clamp(t, 0.0, 1.0));
+    if colormap.colormap == COLORMAP_VIRIDIS {
+        color = viridis(t);
+    } else if colormap.colormap == COLORMAP_INFERNO {
+        color = inferno(t);
+    } else if colormap.colormap == COLORMAP_TURBO {
+        color = turbo(t);
+    }
+    return srgb_to_linear(color);
+}
+
 @fragment
 fn fs_main(
     in: VertexOutput,
 ) -> @location(0) vec4<f32> {
-    return vec4<f32>(0.2, 0.3, 0.4, 1.0);
+    // Loading the texel under the fragment samples nearest neighbour,
+    // so cells stay crisp squares however far the camera zooms in
+    let size = vec2<f32>(f32(params.width), f32(params.height));
+    let texel = min(vec2<i32>(in.uv_coord * size), vec2<i32>(size) - 1);
+    let state = textureLoad(cells, texel, 0);
+
+    // Naga only accepts literals as switch cases, so the modes are told apart with ifs
+    var color = vec3<f32>(state.r);
+    if params.mode == MODE_LIFE || params.mode == MODE_LARGER_THAN_LIFE {
+        // Dying states of Generations rules fade down the colormap after the alive white
+        let cell_state = u32(state.r + 0.5);
+        var states = params.rule.states;
+        if params.mode == MODE_LARGER_THAN_LIFE {
+            states = params.larger_than_life.states;
+        }
+        color = vec3<f32>(f32(cell_state == 1u));
+        if cell_state > 1u {
+            let dying = f32(cell_state - 1u) / f32(states - 1u);
+            color = map_color(1.0 - dying);
+        }
+    } else if params.mode == MODE_PHYSARUM {
+        // Trail is unbounded, compress it into the colormap
+        color = map_color(1.0 - exp(-state.r));
+    } else if params.mode == MODE_GRAY_SCOTT {
+        // V concentration rarely exceeds one half
+        color = map_color(state.g * 2.0);
+    } else if params.mode == MODE_LENIA {
+        color = map_color(state.r);
+    }
+    return vec4<f32>(color, 1.0);
 }
@@ -1,12 +1,20 @@
+use super::{
+    camera::{Camera, CameraUniform},
+    colormap::{Colormap, ColormapUniform},
+};
 use crate::shared::{
-    shader::Shader,
+    shader::{self, Shader},
     sim_params::{SimulationParams, SimulationParamsBuf},
     texture::Texture,
 };
 use wgpu::util::DeviceExt;
 
 pub struct Renderer {
-    bind_group: wgpu::BindGroup,
+    // bind_groups[i] samples the simulation's texture i
+    bind_groups: [wgpu::BindGroup; 2],
+    camera: Camera,
+    camera_buf: wgpu::Buffer,
+    _colormap_buf: wgpu::Buffer,
     pipeline: wgpu::RenderPipeline,
     vertex_buf: wgpu::Buffer,
     index_buf: wgpu::Buffer,
@@ -16,15 +24,17 @@ pub struct Renderer {
 impl Renderer {
     pub fn new(
         device: &wgpu::Device,
-        cell_texture: &Texture,
+        cell_textures: &[Texture; 2],
         sim_params: &SimulationParamsBuf,
         surface_config: &wgpu::SurfaceConfiguration,
+        colormap: Colormap,
     ) -> Self {
+        // The first row of the grid is at the top of the window
         let vertex_data = [
-            Vertex::new([-1, -1], [0, 0]),
-            Vertex::new([1, -1], [1, 0]),
-            Vertex::new([1, 1], [1, 1]),
-            Vertex::new([-1, 1], [0, 1]),
+            Vertex::new([-1, -1], [0, 1]),
+            Vertex::new([1, -1], [1, 1]),
+            Vertex::new([1, 1], [1, 0]),
+            Vertex::new([-1, 1], [0, 0]),
         ];
         #[rustfmt::skip]
         let index_data: [u16; 6] = [
@@ -42,18 +52,39 @@ impl Renderer {
             usage: wgpu::BufferUsages::INDEX,
         });
 
-        let render_shader =
-            Shader::new("src/render/render.wgsl", device).expect("Shader compilation failed!");
+        let grid_size =
+            winit::dpi::PhysicalSize::new(sim_params.params.width, sim_params.params.height);
+        let window_size =
+            winit::dpi::PhysicalSize::new(surface_config.width, surface_config.height);
+        let camera = Camera::new(grid_size, window_size);
+        let camera_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Camera Buffer"),
+            contents: bytemuck::bytes_of(&camera.uniform()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        // Fixed for the lifetime of the renderer
+        let colormap_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Colormap Buffer"),
+            contents: bytemuck::bytes_of(&colormap.uniform()),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let render_shader = Shader::new(
+            "render.wgsl",
+            &[shader::SIM_PARAMS, include_str!("render.wgsl")],
+            device,
+        );
 
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: Some("Render Bind Group Layout"),
             entries: &[
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    ty: wgpu::BindingType::StorageTexture {
-                        access: wgpu::StorageTextureAccess::ReadOnly,
-                        format: cell_texture.texture_format,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
                         view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
                     },
                     visibility: wgpu::ShaderStages::FRAGMENT,
                     count: None,
@@ -70,22 +101,56 @@ impl Renderer {
                     visibility: wgpu::ShaderStages::FRAGMENT,
                     count: None,
                 },
-            ],
-        });
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Render Bind Group"),
-            layout: &bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&cell_texture.texture_view),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<CameraUniform>() as _,
+                        ),
+                    },
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    count: None,
                 },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: sim_params.params_buf.as_entire_binding(),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<ColormapUniform>() as _,
+                        ),
+                    },
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    count: None,
                 },
             ],
         });
+        let bind_groups = cell_textures.each_ref().map(|cell_texture| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Render Bind Group"),
+                layout: &bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&cell_texture.texture_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: sim_params.params_buf.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: camera_buf.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: colormap_buf.as_entire_binding(),
+                    },
+                ],
+            })
+        });
 
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Render Pipeline Layout"),
@@ -122,17 +187,36 @@ impl Renderer {
         Self {
             vertex_buf,
             index_buf,
-            bind_group,
+            bind_groups,
+            camera,
+            camera_buf,
+            _colormap_buf: colormap_buf,
             pipeline,
             _render_shader: render_shader,
         }
     }
 
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    /// Pan and zoom, uploaded on every render
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
     pub fn render(
         &mut self,
         device: &wgpu::Device,
+        queue: &wgpu::Queue,
         surface_texture: &wgpu::SurfaceTexture,
+        current_texture: usize,
     ) -> wgpu::CommandEncoder {
+        queue.write_buffer(
+            &self.camera_buf,
+            0,
+            bytemuck::bytes_of(&self.camera.uniform()),
+        );
         let view = surface_texture
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
@@ -153,7 +237,7 @@ impl Renderer {
         });
         render_pass.push_debug_group("Setup for drawing");
         render_pass.set_pipeline(&self.pipeline);
-        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_bind_group(0, &self.bind_groups[current_texture], &[]);
         render_pass.set_index_buffer(self.index_buf.slice(..), wgpu::IndexFormat::Uint16);
         render_pass.set_vertex_buffer(0, self.vertex_buf.slice(..));
         render_pass.pop_debug_group();
@@ -191,7 +275,7 @@ impl Vertex {
 
     fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
-            array_stride: std::mem::size_of::<SimulationParams>() as wgpu::BufferAddress,
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
             step_mode: wgpu::VertexStepMode::Vertex,
             attributes: &Vertex::ATTR_ARRAY,
         }
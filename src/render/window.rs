@@ -1,59 +1,46 @@
+use std::time::Instant;
 use winit::{
+    dpi::PhysicalPosition,
     event::*,
     event_loop::{ControlFlow, EventLoop},
     window::{Window, WindowBuilder},
 };
 
-use super::renderer::Renderer;
+use super::{brush::Brush, playback::Playback, renderer::Renderer};
+use crate::{compute::simulation::Simulation, shared::gpu::Gpu, snapshot::Snapshot};
 
 pub struct WindowData {
     window: Window,
+    title: String,
     pub size: winit::dpi::PhysicalSize<u32>,
     event_loop: EventLoop<()>,
-    pub device: wgpu::Device,
-    instance: wgpu::Instance,
-    queue: wgpu::Queue,
+    pub gpu: Gpu,
     surface: wgpu::Surface,
     pub surface_config: wgpu::SurfaceConfiguration,
+    /// Present modes supported by the surface, to switch between while running
+    present_modes: Vec<wgpu::PresentMode>,
 }
 
 impl WindowData {
-    pub async fn new(window_title: &str) -> Self {
+    /// Falls back to `Fifo`, which every surface supports, when `present_mode` is not supported
+    pub async fn new(
+        window_title: &str,
+        present_mode: wgpu::PresentMode,
+        backends: wgpu::Backends,
+    ) -> anyhow::Result<Self> {
         let event_loop = EventLoop::new();
         let window = WindowBuilder::new()
             .with_title(window_title)
-            .build(&event_loop)
-            .unwrap();
+            .build(&event_loop)?;
         let size = window.inner_size();
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::all(),
-            dx12_shader_compiler: Default::default(),
-        });
+        let instance = Gpu::instance(backends);
         // Safety:
         // Surface needs to live as long as its window
         // safe because the state owns the surface
-        let surface = unsafe { instance.create_surface(&window) }.unwrap();
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .unwrap();
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
-                    limits: wgpu::Limits::default(),
-                    label: Some("GPU Adapter device"),
-                },
-                None,
-            )
-            .await
-            .unwrap();
+        let surface = unsafe { instance.create_surface(&window) }?;
+        let gpu = Gpu::new(instance, Some(&surface)).await?;
 
-        let surface_caps = surface.get_capabilities(&adapter);
+        let surface_caps = surface.get_capabilities(&gpu.adapter);
         let surface_format = surface_caps
             .formats
             .iter()
@@ -63,36 +50,33 @@ impl WindowData {
         // let surface_format = surface.get_default_config(&adapter, size.width, size.height).unwrap().format;
         // let surface_format = wgpu::TextureFormat::R32Float;
         // let surface_format = wgpu::TextureFormat::Rgba8UnormSrgb;
+        let present_mode = if surface_caps.present_modes.contains(&present_mode) {
+            present_mode
+        } else {
+            log::warn!("Surface does not support {present_mode:?} presentation, using Fifo");
+            wgpu::PresentMode::Fifo
+        };
         let surface_config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
             width: size.width,
             height: size.height,
-            present_mode: surface_caps.present_modes[0],
+            present_mode,
             alpha_mode: surface_caps.alpha_modes[0],
             view_formats: vec![],
         };
-        surface.configure(&device, &surface_config);
+        surface.configure(&gpu.device, &surface_config);
 
-        Self {
+        Ok(Self {
             window,
+            title: window_title.to_owned(),
             size,
             event_loop,
-            device,
-            instance,
-            queue,
+            gpu,
             surface,
             surface_config,
-        }
-    }
-
-    fn _resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
-        if new_size.width > 0 && new_size.height > 0 {
-            self.size = new_size;
-            self.surface_config.width = self.size.width;
-            self.surface_config.height = self.size.height;
-            self.surface.configure(&self.device, &self.surface_config);
-        }
+            present_modes: surface_caps.present_modes,
+        })
     }
 }
 
@@ -100,39 +84,254 @@ pub fn run(
     WindowData {
         // destructured to allow for partial borrows
         window,
+        title,
         size: _size,
         event_loop,
-        device,
-        instance: _instance,
-        queue,
+        gpu:
+            Gpu {
+                instance: _instance,
+                adapter: _,
+                device,
+                queue,
+            },
         surface,
         mut surface_config,
+        present_modes,
     }: WindowData,
     mut renderer: Renderer,
-) {
+    mut simulation: Simulation,
+    // Dragging with the left button paints cells alive, with the right one clears them
+    mut brush: Brush,
+    mut playback: Playback,
+) -> ! {
+    let mut cursor = PhysicalPosition::new(0.0, 0.0);
+    let mut modifiers = ModifiersState::empty();
+    // Dragging with the middle button, or the left one while holding shift, pans the camera
+    let mut panning = false;
+    // Whether a stroke paints or clears, and the cell the brush was last centered on
+    let mut painting: Option<(bool, [i32; 2])> = None;
+    let mut last_frame = Instant::now();
+    let mut shown_title = String::new();
+    // Minimized windows have no area to render to, frames are skipped until they are restored
+    let mut minimized = false;
+
     event_loop.run(move |event, _, control_flow| match event {
-        Event::RedrawRequested(window_id) if window_id == window.id() => {
-            let surface_texture = &surface.get_current_texture().unwrap();
-            let render_command_encoder = renderer.render(&device, &surface_texture);
-            queue.submit(vec![render_command_encoder.finish()].into_iter());
+        Event::RedrawRequested(window_id) if window_id == window.id() && !minimized => {
+            let surface_texture = match surface.get_current_texture() {
+                Ok(surface_texture) => surface_texture,
+                // The surface no longer matches the window, try again with the next frame
+                Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                    surface.configure(&device, &surface_config);
+                    return;
+                }
+                Err(wgpu::SurfaceError::Timeout) => {
+                    log::warn!("Timed out acquiring the next frame, skipping it");
+                    return;
+                }
+                Err(wgpu::SurfaceError::OutOfMemory) => {
+                    log::error!("Out of memory acquiring the next frame, exiting");
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
+            };
+            let now = Instant::now();
+            let generations = playback.advance(now - last_frame);
+            last_frame = now;
+            // Advance the simulation before rendering so the frame shows the latest generation
+            let mut command_buffers: Vec<_> = (0..generations)
+                .map(|_| simulation.step(&device, &queue).finish())
+                .collect();
+            let render_command_encoder =
+                renderer.render(&device, &queue, &surface_texture, simulation.current());
+            command_buffers.push(render_command_encoder.finish());
+            queue.submit(command_buffers);
+            surface_texture.present();
+            // Only touch the title when it changes, not on every frame
+            let new_title = playback.title(&title, simulation.generation);
+            if new_title != shown_title {
+                window.set_title(&new_title);
+                shown_title = new_title;
+            }
+        }
+        // Redraw continuously, paced by the present mode, and sleep until restored when minimized
+        Event::MainEventsCleared if minimized => *control_flow = ControlFlow::Wait,
+        Event::MainEventsCleared => {
+            *control_flow = ControlFlow::Poll;
+            window.request_redraw();
         }
         Event::WindowEvent {
             ref event,
             window_id,
         } if window_id == window.id() => match event {
+            // Only the surface and the view follow the window, the simulation grid is untouched
             WindowEvent::Resized(new_inner_size) => {
-                surface_config.width = new_inner_size.width;
-                surface_config.height = new_inner_size.height;
-                surface.configure(&device, &surface_config);
+                let restored = resize(&surface, &device, &mut surface_config, *new_inner_size);
+                // Time spent minimized is not played back once restored
+                if minimized && restored {
+                    last_frame = Instant::now();
+                }
+                minimized = !restored;
+                renderer.camera_mut().resize(*new_inner_size);
             }
             WindowEvent::ScaleFactorChanged {
                 // scale_factor,
                 new_inner_size,
                 ..
             } => {
-                surface_config.width = new_inner_size.width;
-                surface_config.height = new_inner_size.height;
+                let restored = resize(&surface, &device, &mut surface_config, **new_inner_size);
+                if minimized && restored {
+                    last_frame = Instant::now();
+                }
+                minimized = !restored;
+                renderer.camera_mut().resize(**new_inner_size);
+            }
+            WindowEvent::ModifiersChanged(new_modifiers) => modifiers = *new_modifiers,
+            WindowEvent::CursorMoved { position, .. } => {
+                if panning {
+                    let delta = PhysicalPosition::new(position.x - cursor.x, position.y - cursor.y);
+                    renderer.camera_mut().pan(delta);
+                }
+                cursor = *position;
+                if let Some((alive, last_cell)) = painting {
+                    let cell = grid_cell(&renderer, cursor);
+                    if cell != last_cell {
+                        for center in brush.stroke(last_cell, cell) {
+                            simulation.paint(&queue, brush.spans(center), alive);
+                        }
+                        painting = Some((alive, cell));
+                    }
+                }
+            }
+            WindowEvent::MouseInput { state, button, .. } => match (button, state) {
+                (MouseButton::Middle, ElementState::Pressed) => panning = true,
+                (MouseButton::Left, ElementState::Pressed) if modifiers.shift() => panning = true,
+                (MouseButton::Left | MouseButton::Right, ElementState::Pressed) => {
+                    let alive = *button == MouseButton::Left;
+                    let cell = grid_cell(&renderer, cursor);
+                    simulation.paint(&queue, brush.spans(cell), alive);
+                    painting = Some((alive, cell));
+                }
+                (
+                    MouseButton::Middle | MouseButton::Left | MouseButton::Right,
+                    ElementState::Released,
+                ) => {
+                    panning = false;
+                    painting = None;
+                }
+                _ => {}
+            },
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, lines) => *lines,
+                    // Roughly one line per 40 pixels scrolled on touchpads
+                    MouseScrollDelta::PixelDelta(pixels) => pixels.y as f32 / 40.0,
+                };
+                renderer.camera_mut().zoom_at(cursor, lines);
+            }
+            // F or Home fits the whole grid in the window again
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::F | VirtualKeyCode::Home),
+                        ..
+                    },
+                ..
+            } => {
+                renderer.camera_mut().fit();
+            }
+            // Brackets resize the brush and B switches between a square and a circle
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode:
+                            Some(
+                                key @ (VirtualKeyCode::LBracket
+                                | VirtualKeyCode::RBracket
+                                | VirtualKeyCode::B),
+                            ),
+                        ..
+                    },
+                ..
+            } => {
+                match key {
+                    VirtualKeyCode::LBracket => brush.shrink(),
+                    VirtualKeyCode::RBracket => brush.grow(),
+                    _ => brush.toggle_shape(),
+                }
+                log::info!("Brush: {:?} of radius {}", brush.shape, brush.radius);
+            }
+            // Space pauses, period or right steps one generation and with shift a batch of them,
+            // plus and minus double and halve the generations per frame,
+            // page up and page down double and halve the generations of a batch
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode:
+                            Some(
+                                key @ (VirtualKeyCode::Space
+                                | VirtualKeyCode::Period
+                                | VirtualKeyCode::Right
+                                | VirtualKeyCode::Equals
+                                | VirtualKeyCode::Plus
+                                | VirtualKeyCode::NumpadAdd
+                                | VirtualKeyCode::Minus
+                                | VirtualKeyCode::NumpadSubtract
+                                | VirtualKeyCode::PageUp
+                                | VirtualKeyCode::PageDown),
+                            ),
+                        ..
+                    },
+                ..
+            } => match key {
+                VirtualKeyCode::Space => playback.toggle_pause(),
+                VirtualKeyCode::Period | VirtualKeyCode::Right if modifiers.shift() => {
+                    playback.step_batch()
+                }
+                VirtualKeyCode::Period | VirtualKeyCode::Right => playback.step(1),
+                VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => playback.slower(),
+                VirtualKeyCode::PageUp => playback.grow_batch(),
+                VirtualKeyCode::PageDown => playback.shrink_batch(),
+                _ => playback.faster(),
+            },
+            // V switches to the next present mode the surface supports
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::V),
+                        ..
+                    },
+                ..
+            } => {
+                let index = present_modes
+                    .iter()
+                    .position(|&mode| mode == surface_config.present_mode)
+                    .map_or(0, |index| (index + 1) % present_modes.len());
+                surface_config.present_mode = present_modes[index];
                 surface.configure(&device, &surface_config);
+                log::info!("Presenting with {:?}", surface_config.present_mode);
+            }
+            // S saves a snapshot of the whole state into the working directory
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state: ElementState::Pressed,
+                        virtual_keycode: Some(VirtualKeyCode::S),
+                        ..
+                    },
+                ..
+            } => {
+                let path =
+                    std::path::PathBuf::from(format!("generation-{}.snap", simulation.generation));
+                match Snapshot::capture(&device, &queue, &simulation)
+                    .and_then(|snapshot| snapshot.save(&path))
+                {
+                    Ok(()) => log::info!("Saved snapshot {}", path.display()),
+                    Err(error) => log::error!("{error:#}"),
+                }
             }
             // Window close event or Escape key pressed: Exit
             WindowEvent::CloseRequested
@@ -150,3 +349,28 @@ pub fn run(
         _ => {}
     })
 }
+
+/// Cell of the grid under a position in the window
+fn grid_cell(renderer: &Renderer, position: PhysicalPosition<f64>) -> [i32; 2] {
+    renderer
+        .camera()
+        .window_to_grid(position)
+        .map(|coordinate| coordinate.floor() as i32)
+}
+
+/// Reconfigures the surface for a new window size, unless the window has no area as when minimized.
+/// Returns whether there is a surface to render to
+fn resize(
+    surface: &wgpu::Surface,
+    device: &wgpu::Device,
+    surface_config: &mut wgpu::SurfaceConfiguration,
+    new_size: winit::dpi::PhysicalSize<u32>,
+) -> bool {
+    if new_size.width == 0 || new_size.height == 0 {
+        return false;
+    }
+    surface_config.width = new_size.width;
+    surface_config.height = new_size.height;
+    surface.configure(device, surface_config);
+    true
+}
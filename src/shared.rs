@@ -1,3 +1,8 @@
+pub mod boundary;
+pub mod gpu;
+pub mod larger_than_life;
+pub mod rng;
+pub mod rule;
 pub mod shader;
 pub mod sim_params;
 /// Defines functionality and types shared between render and compute
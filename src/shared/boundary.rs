@@ -0,0 +1,507 @@
+use anyhow::bail;
+use std::str::FromStr;
+
+/// What lies beyond the edges of the grid, for every cell that reads its neighbors
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Boundary {
+    /// Opposite edges are joined
+    #[default]
+    Torus,
+    /// Cells beyond the edges are always dead, or zero in every channel
+    Dead,
+    /// Cells beyond the edges are always alive, or one in every channel
+    Alive,
+    /// The grid is reflected at every edge, repeating the edge cells
+    Mirror,
+    /// Left and right edges are joined, top and bottom are joined with a twist
+    KleinBottle,
+    /// Both pairs of opposite edges are joined with a twist, making a real projective plane
+    CrossSurface,
+}
+
+impl Boundary {
+    /// Every boundary, indexed by the discriminant stored in the uniform
+    pub const ALL: [Self; 6] = [
+        Self::Torus,
+        Self::Dead,
+        Self::Alive,
+        Self::Mirror,
+        Self::KleinBottle,
+        Self::CrossSurface,
+    ];
+
+    /// Cell of the grid standing in for the cell at `(x, y)`, which may lie beyond the edges,
+    /// or `None` when a fixed edge decides the cell instead. Matches `boundary_texel` in
+    /// `boundary.wgsl`
+    pub fn texel(&self, x: isize, y: isize, width: usize, height: usize) -> Option<(usize, usize)> {
+        let (width, height) = (width as isize, height as isize);
+        let wraps = (x.div_euclid(width), y.div_euclid(height));
+        let (mut texel_x, mut texel_y) = (x.rem_euclid(width), y.rem_euclid(height));
+        // Crossing an edge an odd number of times reflects the axes its twist reflects
+        let (flip_x, flip_y) = match self {
+            Self::Torus => (false, false),
+            Self::Dead | Self::Alive if wraps != (0, 0) => return None,
+            Self::Dead | Self::Alive => (false, false),
+            Self::Mirror => (wraps.0 % 2 != 0, wraps.1 % 2 != 0),
+            Self::KleinBottle => (wraps.1 % 2 != 0, false),
+            Self::CrossSurface => (wraps.1 % 2 != 0, wraps.0 % 2 != 0),
+        };
+        if flip_x {
+            texel_x = width - 1 - texel_x;
+        }
+        if flip_y {
+            texel_y = height - 1 - texel_y;
+        }
+        Some((texel_x as usize, texel_y as usize))
+    }
+
+    /// Value of every channel of the cells beyond a fixed edge
+    pub fn edge_value(&self) -> f32 {
+        (*self == Self::Alive) as u8 as f32
+    }
+}
+
+impl FromStr for Boundary {
+    type Err = anyhow::Error;
+
+    fn from_str(boundary: &str) -> anyhow::Result<Self> {
+        Ok(match boundary.to_ascii_lowercase().as_str() {
+            "torus" | "wrap" => Self::Torus,
+            "dead" => Self::Dead,
+            "alive" => Self::Alive,
+            "mirror" | "reflect" => Self::Mirror,
+            "klein" | "klein-bottle" => Self::KleinBottle,
+            "cross" | "cross-surface" => Self::CrossSurface,
+            _ => bail!(
+                "Unknown boundary '{boundary}', \
+                expected torus, dead, alive, mirror, klein-bottle or cross-surface"
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::{
+        compute::{
+            cpu_simulation::CpuSimulation,
+            simulation::{
+                tests::{compute_gpu, run},
+                ComputeBackend,
+            },
+        },
+        shared::{
+            gpu::Gpu,
+            rng::Rng,
+            sim_params::{SimulationMode, SimulationParams},
+        },
+    };
+
+    pub const WIDTH: isize = 20;
+    pub const HEIGHT: isize = 16;
+
+    /// Generations a glider takes to move one cell diagonally
+    const PERIOD: usize = 4;
+
+    /// A glider heading right and down, relative to its top left
+    const GLIDER: [(isize, isize); 5] = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+
+    #[derive(Copy, Clone, Debug)]
+    enum Edge {
+        Left,
+        Right,
+        Top,
+        Bottom,
+    }
+
+    impl Edge {
+        const ALL: [Self; 4] = [Self::Left, Self::Right, Self::Top, Self::Bottom];
+
+        /// Top left and diagonal heading of a glider 3 cells from the edge, which after
+        /// `8 * PERIOD` generations lies 3 cells beyond it, far from the other edges
+        fn glider(&self) -> ((isize, isize), (isize, isize)) {
+            match self {
+                Self::Left => ((2, 3), (-1, 1)),
+                Self::Right => ((WIDTH - 5, 3), (1, 1)),
+                Self::Top => ((5, 2), (1, -1)),
+                Self::Bottom => ((5, HEIGHT - 5), (1, 1)),
+            }
+        }
+
+        /// Whether the glider crosses the left or right edge
+        fn sideways(&self) -> bool {
+            matches!(self, Self::Left | Self::Right)
+        }
+    }
+
+    /// Cells of the glider crossing `edge` after `generations`, on the plane beyond the grid
+    fn glider_cells(edge: Edge, generations: usize) -> Vec<(isize, isize)> {
+        let ((left, top), (dx, dy)) = edge.glider();
+        let moved = (generations / PERIOD) as isize;
+        GLIDER
+            .iter()
+            .map(|&(x, y)| {
+                // Reflections of the glider head the other ways
+                let x = if dx < 0 { 2 - x } else { x };
+                let y = if dy < 0 { 2 - y } else { y };
+                (left + x + dx * moved, top + y + dy * moved)
+            })
+            .collect()
+    }
+
+    pub fn params_of(
+        mode: SimulationMode,
+        boundary: Boundary,
+        width: isize,
+        height: isize,
+    ) -> SimulationParams {
+        let size = winit::dpi::PhysicalSize::new(width as u32, height as u32);
+        let mut params = SimulationParams::new(&size, mode);
+        params.set_boundary(boundary);
+        params
+    }
+
+    /// Conway's Life under `boundary`
+    fn params(boundary: Boundary, width: isize, height: isize) -> SimulationParams {
+        params_of(SimulationMode::Life, boundary, width, height)
+    }
+
+    fn grid(width: isize, height: isize, alive: &[(isize, isize)]) -> Vec<f32> {
+        let mut cells = vec![0.0; (width * height) as usize];
+        for &(x, y) in alive {
+            assert!((0..width).contains(&x) && (0..height).contains(&y));
+            cells[(y * width + x) as usize] = 1.0;
+        }
+        cells
+    }
+
+    /// Steps the glider crossing `edge` on the grid bounded by `boundary`
+    fn cross(boundary: Boundary, edge: Edge, generations: usize) -> Vec<f32> {
+        let mut simulation = CpuSimulation::new(&params(boundary, WIDTH, HEIGHT));
+        simulation.set_cells(&grid(WIDTH, HEIGHT, &glider_cells(edge, 0)));
+        for _ in 0..generations {
+            simulation.step();
+        }
+        simulation.cells().to_vec()
+    }
+
+    /// Cells of the glider past the edge, placed back on the grid by `place`
+    fn placed(edge: Edge, place: impl Fn((isize, isize)) -> (isize, isize)) -> Vec<f32> {
+        let cells: Vec<_> = glider_cells(edge, 8 * PERIOD)
+            .into_iter()
+            .map(place)
+            .collect();
+        grid(WIDTH, HEIGHT, &cells)
+    }
+
+    fn wrap((x, y): (isize, isize)) -> (isize, isize) {
+        (x.rem_euclid(WIDTH), y.rem_euclid(HEIGHT))
+    }
+
+    fn flip_x((x, y): (isize, isize)) -> (isize, isize) {
+        (WIDTH - 1 - x, y)
+    }
+
+    fn flip_y((x, y): (isize, isize)) -> (isize, isize) {
+        (x, HEIGHT - 1 - y)
+    }
+
+    #[test]
+    fn torus_wraps_gliders() {
+        for edge in Edge::ALL {
+            assert_eq!(
+                cross(Boundary::Torus, edge, 8 * PERIOD),
+                placed(edge, wrap),
+                "{edge:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn klein_bottle_flips_gliders_across_top_and_bottom() {
+        for edge in Edge::ALL {
+            let expected = match edge.sideways() {
+                true => placed(edge, wrap),
+                false => placed(edge, |cell| flip_x(wrap(cell))),
+            };
+            assert_eq!(
+                cross(Boundary::KleinBottle, edge, 8 * PERIOD),
+                expected,
+                "{edge:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn cross_surface_flips_gliders_across_every_edge() {
+        for edge in Edge::ALL {
+            let expected = match edge.sideways() {
+                true => placed(edge, |cell| flip_y(wrap(cell))),
+                false => placed(edge, |cell| flip_x(wrap(cell))),
+            };
+            assert_eq!(
+                cross(Boundary::CrossSurface, edge, 8 * PERIOD),
+                expected,
+                "{edge:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn dead_edges_destroy_gliders() {
+        for edge in Edge::ALL {
+            let cells = cross(Boundary::Dead, edge, 16 * PERIOD);
+            // Whatever is left is still, against the edge the glider hit
+            let mut simulation = CpuSimulation::new(&params(Boundary::Dead, WIDTH, HEIGHT));
+            simulation.set_cells(&cells);
+            simulation.step();
+            assert_eq!(simulation.cells(), cells, "{edge:?}");
+            for (index, _) in cells.iter().enumerate().filter(|(_, &cell)| cell == 1.0) {
+                let (x, y) = (index as isize % WIDTH, index as isize / WIDTH);
+                let distance = match edge {
+                    Edge::Left => x,
+                    Edge::Right => WIDTH - 1 - x,
+                    Edge::Top => y,
+                    Edge::Bottom => HEIGHT - 1 - y,
+                };
+                assert!(distance < 3, "{edge:?}: ({x}, {y}) is away from the edge");
+            }
+            assert!(cells.iter().filter(|&&cell| cell == 1.0).count() < GLIDER.len());
+        }
+    }
+
+    /// Gliders meet their own reflection at mirror edges, so the grid steps as the top left
+    /// quarter of a torus twice as wide and high holding the grid and its reflections
+    #[test]
+    fn mirror_reflects_gliders() {
+        for edge in Edge::ALL {
+            let generations = 8 * PERIOD;
+            let mut unfolded = CpuSimulation::new(&params(Boundary::Torus, 2 * WIDTH, 2 * HEIGHT));
+            let reflections: Vec<_> = glider_cells(edge, 0)
+                .into_iter()
+                .flat_map(|(x, y)| {
+                    let (mirrored_x, mirrored_y) = (2 * WIDTH - 1 - x, 2 * HEIGHT - 1 - y);
+                    [
+                        (x, y),
+                        (mirrored_x, y),
+                        (x, mirrored_y),
+                        (mirrored_x, mirrored_y),
+                    ]
+                })
+                .collect();
+            unfolded.set_cells(&grid(2 * WIDTH, 2 * HEIGHT, &reflections));
+            for _ in 0..generations {
+                unfolded.step();
+            }
+            let quarter: Vec<f32> = unfolded
+                .cells()
+                .chunks(2 * WIDTH as usize)
+                .take(HEIGHT as usize)
+                .flat_map(|row| &row[..WIDTH as usize])
+                .copied()
+                .collect();
+
+            let cells = cross(Boundary::Mirror, edge, generations);
+            assert_eq!(cells, quarter, "{edge:?}");
+            // Nothing reaches the opposite edge
+            assert_ne!(cells, placed(edge, wrap), "{edge:?}");
+        }
+    }
+
+    /// Edge cells of an empty grid see three alive cells beyond the edge and are born,
+    /// corners see five and are not
+    #[test]
+    fn alive_edges_bear_cells() {
+        let (width, height) = (6, 5);
+        let mut simulation = CpuSimulation::new(&params(Boundary::Alive, width, height));
+        simulation.step();
+        let on_edge = |x: isize, y: isize| x == 0 || y == 0 || x == width - 1 || y == height - 1;
+        let corner = |x: isize, y: isize| (x == 0 || x == width - 1) && (y == 0 || y == height - 1);
+        let born: Vec<_> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&(x, y)| on_edge(x, y) && !corner(x, y))
+            .collect();
+        assert_eq!(simulation.cells(), grid(width, height, &born));
+    }
+
+    #[test]
+    fn texel_maps_coordinates_beyond_the_edges() {
+        let (width, height) = (5, 4);
+        let texel = |boundary: Boundary, x, y| boundary.texel(x, y, width, height);
+        for boundary in Boundary::ALL {
+            // Inside the grid every boundary reads the cell itself
+            assert_eq!(texel(boundary, 0, 0), Some((0, 0)));
+            assert_eq!(texel(boundary, 4, 3), Some((4, 3)));
+            assert_eq!(texel(boundary, 2, 1), Some((2, 1)));
+        }
+
+        assert_eq!(texel(Boundary::Torus, -1, 0), Some((4, 0)));
+        assert_eq!(texel(Boundary::Torus, 5, 3), Some((0, 3)));
+        assert_eq!(texel(Boundary::Torus, 1, -1), Some((1, 3)));
+        assert_eq!(texel(Boundary::Torus, -6, 9), Some((4, 1)));
+
+        for boundary in [Boundary::Dead, Boundary::Alive] {
+            for (x, y) in [(-1, 0), (5, 0), (0, -1), (0, 4), (-1, -1), (100, 2)] {
+                assert_eq!(texel(boundary, x, y), None);
+            }
+        }
+        assert_eq!(Boundary::Dead.edge_value(), 0.0);
+        assert_eq!(Boundary::Alive.edge_value(), 1.0);
+
+        // Edge cells are repeated, then the grid runs backwards
+        assert_eq!(texel(Boundary::Mirror, -1, 2), Some((0, 2)));
+        assert_eq!(texel(Boundary::Mirror, -2, 2), Some((1, 2)));
+        assert_eq!(texel(Boundary::Mirror, 5, 2), Some((4, 2)));
+        assert_eq!(texel(Boundary::Mirror, 7, 2), Some((2, 2)));
+        assert_eq!(texel(Boundary::Mirror, 1, -1), Some((1, 0)));
+        assert_eq!(texel(Boundary::Mirror, 1, 5), Some((1, 2)));
+        assert_eq!(texel(Boundary::Mirror, -1, -1), Some((0, 0)));
+        assert_eq!(texel(Boundary::Mirror, 10, 1), Some((0, 1)));
+
+        // Crossing top or bottom reflects x, crossing left or right doesn't
+        assert_eq!(texel(Boundary::KleinBottle, -1, 1), Some((4, 1)));
+        assert_eq!(texel(Boundary::KleinBottle, 5, 1), Some((0, 1)));
+        assert_eq!(texel(Boundary::KleinBottle, 1, -1), Some((3, 3)));
+        assert_eq!(texel(Boundary::KleinBottle, 1, 4), Some((3, 0)));
+        assert_eq!(texel(Boundary::KleinBottle, 1, 8), Some((1, 0)));
+
+        // Crossing left or right also reflects y
+        assert_eq!(texel(Boundary::CrossSurface, -1, 1), Some((4, 2)));
+        assert_eq!(texel(Boundary::CrossSurface, 5, 0), Some((0, 3)));
+        assert_eq!(texel(Boundary::CrossSurface, 1, -1), Some((3, 3)));
+        assert_eq!(texel(Boundary::CrossSurface, 1, 4), Some((3, 0)));
+        assert_eq!(texel(Boundary::CrossSurface, -1, -1), Some((0, 0)));
+    }
+
+    /// Soups stepped under every boundary by `boundary_texel` in `boundary.wgsl`
+    /// and by [`Boundary::texel`], compared cell by cell
+    #[tokio::test]
+    async fn gpu_matches_cpu() {
+        let Some(gpu) = compute_gpu().await else {
+            return;
+        };
+        let mut rng = Rng::new(3);
+        let soup: Vec<f32> = (0..WIDTH * HEIGHT)
+            .map(|_| (rng.next_f32() < 0.4) as u8 as f32)
+            .collect();
+        for boundary in Boundary::ALL {
+            for (mode, rule) in [
+                (SimulationMode::Life, "B3/S23"),
+                (
+                    SimulationMode::LargerThanLife,
+                    "R3,C0,M1,S14..24,B14..19,NM",
+                ),
+            ] {
+                let mut params = params_of(mode, boundary, WIDTH, HEIGHT);
+                params.set_rule(rule).unwrap();
+                let mut simulation = CpuSimulation::new(&params);
+                simulation.set_cells(&soup);
+                for steps in 1..=4 {
+                    simulation.step();
+                    assert_eq!(
+                        run(&gpu, params, &soup, steps, ComputeBackend::Gpu),
+                        simulation.cells(),
+                        "{boundary:?} {rule} after {steps} generations"
+                    );
+                }
+            }
+        }
+    }
+
+    /// Size of a torus that unfolds `boundary`, and the cells of the torus standing for
+    /// the cell at `(x, y)`, whose values stay equal under kernels symmetric in both axes
+    fn unfolded(boundary: Boundary, x: isize, y: isize) -> ((isize, isize), Vec<(isize, isize)>) {
+        let (w, h) = (WIDTH, HEIGHT);
+        match boundary {
+            Boundary::Mirror => (
+                (2 * w, 2 * h),
+                vec![
+                    (x, y),
+                    (2 * w - 1 - x, y),
+                    (x, 2 * h - 1 - y),
+                    (2 * w - 1 - x, 2 * h - 1 - y),
+                ],
+            ),
+            Boundary::KleinBottle => ((w, 2 * h), vec![(x, y), (w - 1 - x, y + h)]),
+            Boundary::CrossSurface => (
+                (2 * w, 2 * h),
+                vec![
+                    (x, y),
+                    (x + w, h - 1 - y),
+                    (w - 1 - x, y + h),
+                    (2 * w - 1 - x, 2 * h - 1 - y),
+                ],
+            ),
+            _ => ((w, h), vec![(x, y)]),
+        }
+    }
+
+    /// Texels of a blob in the top left corner, uneven in both axes so reflections show,
+    /// on a `width` by `height` grid of `channels` f32 each
+    fn blob(
+        mode: SimulationMode,
+        channels: usize,
+        (width, height): (isize, isize),
+        copies: impl Fn(isize, isize) -> Vec<(isize, isize)>,
+    ) -> Vec<f32> {
+        let mut texels = vec![mode.blended_cell(0.0); (width * height) as usize];
+        for y in 0..3 {
+            for x in 0..4 {
+                let value = 0.3 + 0.1 * x as f32 + 0.15 * y as f32;
+                for (x, y) in copies(x, y) {
+                    texels[(y * width + x) as usize] = mode.blended_cell(value);
+                }
+            }
+        }
+        texels
+            .iter()
+            .flat_map(|texel| {
+                (0..channels).map(|channel| texel.get(channel).copied().unwrap_or(0.0))
+            })
+            .collect()
+    }
+
+    /// A blob against two edges, stepped once by `mode` with parameters set by `adjust` under
+    /// twisted and mirrored boundaries, matches the grid unfolded onto a torus holding
+    /// its reflections, and differs from the blob stepped on a torus of the grid itself
+    pub fn assert_reads_across_edges(
+        gpu: &Gpu,
+        mode: SimulationMode,
+        adjust: impl Fn(&mut SimulationParams),
+    ) {
+        let channels = mode.texture_format(&gpu.adapter).block_size(None).unwrap() as usize
+            / std::mem::size_of::<f32>();
+        let step = |boundary, (width, height), cells: &[f32]| {
+            let mut params = params_of(mode, boundary, width, height);
+            adjust(&mut params);
+            run(gpu, params, cells, 1, ComputeBackend::Gpu)
+        };
+        let cells = blob(mode, channels, (WIDTH, HEIGHT), |x, y| vec![(x, y)]);
+        let torus = step(Boundary::Torus, (WIDTH, HEIGHT), &cells);
+        for boundary in [
+            Boundary::Mirror,
+            Boundary::KleinBottle,
+            Boundary::CrossSurface,
+        ] {
+            let bounded = step(boundary, (WIDTH, HEIGHT), &cells);
+            let (size, _) = unfolded(boundary, 0, 0);
+            let unfolded_cells = blob(mode, channels, size, |x, y| unfolded(boundary, x, y).1);
+            let expected: Vec<f32> = step(Boundary::Torus, size, &unfolded_cells)
+                .chunks(size.0 as usize * channels)
+                .take(HEIGHT as usize)
+                .flat_map(|row| &row[..WIDTH as usize * channels])
+                .copied()
+                .collect();
+            let difference = |other: &[f32]| {
+                bounded
+                    .iter()
+                    .zip(other)
+                    .map(|(a, b)| (a - b).abs())
+                    .fold(0.0, f32::max)
+            };
+            assert!(difference(&expected) < 1e-5, "{mode:?} {boundary:?}");
+            assert!(difference(&torus) > 1e-3, "{mode:?} {boundary:?}");
+        }
+    }
+}
@@ -0,0 +1,45 @@
+// Prepended after sim_params.wgsl to every compute shader that reads neighboring cells,
+// must match Boundary::texel in boundary.rs
+
+// Floored division, since the remainder of a negative integer differs between backends
+fn floor_div(a: i32, b: i32) -> i32 {
+    if a >= 0 {
+        return a / b;
+    }
+    return -((-a - 1) / b) - 1;
+}
+
+// Texel standing in for the cell at pos, which may lie beyond the edges of the grid,
+// or -1 when a fixed edge decides the cell instead
+fn boundary_texel(pos: vec2<i32>) -> vec2<i32> {
+    let size = vec2<i32>(i32(params.width), i32(params.height));
+    let wraps = vec2<i32>(floor_div(pos.x, size.x), floor_div(pos.y, size.y));
+    let odd = (wraps & vec2<i32>(1)) != vec2<i32>(0);
+    let texel = pos - wraps * size;
+    let flipped = size - 1 - texel;
+
+    // Crossing an edge an odd number of times reflects the axes its twist reflects
+    let boundary = params.boundary;
+    if boundary == BOUNDARY_DEAD || boundary == BOUNDARY_ALIVE {
+        return select(texel, vec2<i32>(-1), any(wraps != vec2<i32>(0)));
+    }
+    if boundary == BOUNDARY_MIRROR {
+        return select(texel, flipped, odd);
+    }
+    if boundary == BOUNDARY_KLEIN_BOTTLE {
+        return select(texel, flipped, vec2<bool>(odd.y, false));
+    }
+    if boundary == BOUNDARY_CROSS_SURFACE {
+        return select(texel, flipped, odd.yx);
+    }
+    return texel;
+}
+
+// Loads the cell at pos, which may lie beyond the edges of the grid
+fn load_cell(cells: texture_2d<f32>, pos: vec2<i32>) -> vec4<f32> {
+    let texel = boundary_texel(pos);
+    if texel.x < 0 {
+        return vec4<f32>(f32(params.boundary == BOUNDARY_ALIVE));
+    }
+    return textureLoad(cells, texel, 0);
+}
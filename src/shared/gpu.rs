@@ -0,0 +1,63 @@
+use anyhow::{Context, Result};
+
+/// Adapter and device to run the simulation on, with or without a window to present to
+pub struct Gpu {
+    pub instance: wgpu::Instance,
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+}
+
+impl Gpu {
+    pub fn instance(backends: wgpu::Backends) -> wgpu::Instance {
+        wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends,
+            dx12_shader_compiler: Default::default(),
+        })
+    }
+
+    /// Requests a device from an adapter of the instance that can present to the surface, if any
+    pub async fn new(
+        instance: wgpu::Instance,
+        compatible_surface: Option<&wgpu::Surface>,
+    ) -> Result<Self> {
+        let mut adapter = None;
+        // Prefer a hardware adapter, falling back to a software one
+        for force_fallback_adapter in [false, true] {
+            adapter = instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference: wgpu::PowerPreference::default(),
+                    compatible_surface,
+                    force_fallback_adapter,
+                })
+                .await;
+            if adapter.is_some() {
+                break;
+            }
+        }
+        let adapter = adapter.context(match compatible_surface {
+            Some(_) => "No adapter compatible with the window surface",
+            None => "No adapter available",
+        })?;
+        log::info!("Using adapter {:?}", adapter.get_info());
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    features: adapter.features()
+                        & wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES,
+                    limits: adapter.limits(),
+                    label: Some("GPU Adapter device"),
+                },
+                None,
+            )
+            .await
+            .context("Failed to create a device")?;
+
+        Ok(Self {
+            instance,
+            adapter,
+            device,
+            queue,
+        })
+    }
+}
@@ -0,0 +1,224 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::{fmt, str::FromStr};
+
+/// Evans' Larger than Life rule: like Life-like rules, but counting the alive cells
+/// within `range` of each cell and comparing the count against a birth and a survival interval.
+///
+/// Cells hold their state number as with Generations [`Rule`](super::rule::Rule)s.
+/// Laid out to match the `larger_than_life` member of `SimulationParams` in the shaders.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LargerThanLifeRule {
+    range: u32,
+    states: u32,
+    /// Nonzero when a cell counts itself
+    middle: u32,
+    neighborhood: u32,
+    /// Inclusive bounds on the count
+    survival: [u32; 2],
+    birth: [u32; 2],
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Neighborhood {
+    /// Square of side `2 * range + 1`
+    Moore,
+    /// Diamond of cells within Manhattan distance `range`
+    VonNeumann,
+    /// Disk of cells within Euclidean distance `range + 1/2`
+    Circular,
+}
+
+/// Golly limits the range to 500
+const MAX_RANGE: u32 = 500;
+
+/// Generations rules have at most this many states, as in Golly
+const MAX_STATES: u32 = 256;
+
+impl Neighborhood {
+    /// Every neighborhood, indexed by the discriminant stored in the uniform
+    const ALL: [Self; 3] = [Self::Moore, Self::VonNeumann, Self::Circular];
+
+    fn letter(&self) -> char {
+        match self {
+            Self::Moore => 'M',
+            Self::VonNeumann => 'N',
+            Self::Circular => 'C',
+        }
+    }
+}
+
+impl LargerThanLifeRule {
+    /// Fails for rules, parsed or read as bytes, with fields out of their bounds
+    pub fn check(&self) -> Result<()> {
+        if !(1..=MAX_RANGE).contains(&self.range) {
+            bail!(
+                "Expected a range from 1 to {MAX_RANGE}, found {}",
+                self.range
+            );
+        }
+        if !(2..=MAX_STATES).contains(&self.states) {
+            bail!("Expected 2 to {MAX_STATES} states, found {}", self.states);
+        }
+        if self.middle > 1 {
+            bail!("Expected M0 or M1, found M{}", self.middle);
+        }
+        if self.neighborhood as usize >= Neighborhood::ALL.len() {
+            bail!("Unknown neighborhood {}", self.neighborhood);
+        }
+        for [min, max] in [self.survival, self.birth] {
+            if min > max {
+                bail!("Interval {min}..{max} is empty");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn range(&self) -> u32 {
+        self.range
+    }
+
+    pub fn states(&self) -> u32 {
+        self.states
+    }
+
+    pub fn neighborhood(&self) -> Neighborhood {
+        Neighborhood::ALL[self.neighborhood as usize]
+    }
+
+    /// Half-width of the neighborhood in the row `dy` away from the cell, matching `row_extent`
+    /// in `larger_than_life.wgsl`
+    pub fn row_extent(&self, dy: i32) -> i32 {
+        let range = self.range as i32;
+        match self.neighborhood() {
+            Neighborhood::Moore => range,
+            Neighborhood::VonNeumann => range - dy.abs(),
+            Neighborhood::Circular => {
+                // Largest extent with extent^2 + dy^2 < (range + 1/2)^2, in integers
+                let limit = range * range + range - dy * dy;
+                let mut extent = (limit.max(0) as f32).sqrt() as i32;
+                while (extent + 1) * (extent + 1) <= limit {
+                    extent += 1;
+                }
+                while extent >= 0 && extent * extent > limit {
+                    extent -= 1;
+                }
+                extent
+            }
+        }
+    }
+
+    /// State of a cell in the next generation, given its state and the alive cells it counts
+    pub fn next_state(&self, state: u32, count: u32) -> u32 {
+        let within = |[min, max]: [u32; 2]| (min..=max).contains(&count);
+        match state {
+            0 => within(self.birth) as u32,
+            1 if within(self.survival) => 1,
+            // Failing to survive starts dying, and the last dying state dies
+            _ => (state + 1) % self.states,
+        }
+    }
+
+    /// Whether a cell in this state counts as alive for its own neighborhood
+    pub fn counts_middle(&self) -> bool {
+        self.middle != 0
+    }
+}
+
+impl Default for LargerThanLifeRule {
+    /// Bosco's rule, with its own gliders
+    fn default() -> Self {
+        "R5,C0,M1,S34..58,B34..45,NM".parse().unwrap()
+    }
+}
+
+/// Parses an inclusive interval of counts like `34..58`
+fn parse_interval(interval: &str) -> Result<[u32; 2]> {
+    let (min, max) = interval
+        .split_once("..")
+        .ok_or_else(|| anyhow!("Expected an interval like 34..58, found '{interval}'"))?;
+    let parse = |count: &str| {
+        count
+            .parse::<u32>()
+            .with_context(|| format!("Expected a count, found '{count}'"))
+    };
+    let (min, max) = (parse(min)?, parse(max)?);
+    if min > max {
+        bail!("Interval {min}..{max} is empty");
+    }
+    Ok([min, max])
+}
+
+impl FromStr for LargerThanLifeRule {
+    type Err = anyhow::Error;
+
+    /// Accepts Golly's `R5,C0,M1,S34..58,B34..45,NM` notation: range, states (0 or 1 mean 2),
+    /// whether the middle cell counts, survival and birth intervals, and Moore, von Neumann or
+    /// circular neighborhood. Range, survival and birth are required, the rest default to
+    /// `C0`, `M0` and `NM`
+    fn from_str(rulestring: &str) -> Result<Self> {
+        let parse = || -> Result<Self> {
+            let mut range = None;
+            let mut states = 2;
+            let mut middle = 0;
+            let mut survival = None;
+            let mut birth = None;
+            let mut neighborhood = Neighborhood::Moore;
+            for section in rulestring.trim().split(',') {
+                let mut chars = section.chars();
+                let key = chars.next().map(|c| c.to_ascii_uppercase());
+                let value = chars.as_str();
+                let number = || {
+                    value
+                        .parse::<u32>()
+                        .with_context(|| format!("Expected a number in '{section}'"))
+                };
+                match key {
+                    Some('R') => range = Some(number()?),
+                    Some('C') => states = number()?.max(2),
+                    Some('M') => middle = number()?,
+                    Some('S') => survival = Some(parse_interval(value)?),
+                    Some('B') => birth = Some(parse_interval(value)?),
+                    Some('N') => {
+                        neighborhood = Neighborhood::ALL
+                            .into_iter()
+                            .find(|n| value.eq_ignore_ascii_case(&n.letter().to_string()))
+                            .ok_or_else(|| {
+                                anyhow!("Expected neighborhood NM, NN or NC, found '{section}'")
+                            })?
+                    }
+                    _ => bail!("Unexpected section '{section}'"),
+                }
+            }
+
+            let rule = Self {
+                range: range.ok_or_else(|| anyhow!("Missing range 'R'"))?,
+                states,
+                middle,
+                neighborhood: neighborhood as u32,
+                survival: survival.ok_or_else(|| anyhow!("Missing survival interval 'S'"))?,
+                birth: birth.ok_or_else(|| anyhow!("Missing birth interval 'B'"))?,
+            };
+            rule.check()?;
+            Ok(rule)
+        };
+        parse().with_context(|| format!("Failed to parse Larger than Life rule '{rulestring}'"))
+    }
+}
+
+impl fmt::Display for LargerThanLifeRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "R{},C{},M{},S{}..{},B{}..{},N{}",
+            self.range,
+            if self.states > 2 { self.states } else { 0 },
+            self.middle,
+            self.survival[0],
+            self.survival[1],
+            self.birth[0],
+            self.birth[1],
+            self.neighborhood().letter()
+        )
+    }
+}
@@ -0,0 +1,474 @@
+use anyhow::{anyhow, bail, Context, Result};
+use std::{fmt, str::FromStr};
+
+/// Life-like or Generations rule, stored as birth and survival lookup tables over every
+/// configuration of the 8 neighbors so isotropic non-totalistic rules need no special casing.
+///
+/// Bit `i` of a neighborhood mask is set when the neighbor at `NEIGHBORS[i]` is alive.
+/// Cells hold their state number: 0 is dead, 1 is alive, and with more than two states
+/// cells that fail to survive count up through the dying states before returning to 0.
+/// Laid out to match the `rule` member of `SimulationParams` in the shaders.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Rule {
+    birth: [u32; 8],
+    survival: [u32; 8],
+    states: u32,
+    _padding: [u32; 3],
+}
+
+/// Generations rules have at most this many states, as in Golly
+const MAX_STATES: u32 = 256;
+
+/// Neighbor offsets in clockwise order starting from north, with y pointing down
+pub const NEIGHBORS: [(isize, isize); 8] = [
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+
+/// Hensel notation: a representative neighborhood for every letter,
+/// with bits in the order of `NEIGHBORS`. Counts above 4 use the complements of counts below 4.
+const HENSEL_LETTERS: [&[(char, u8)]; 5] = [
+    &[],
+    &[('c', 0b00000010), ('e', 0b00000001)],
+    &[
+        ('c', 0b00001010),
+        ('e', 0b00000101),
+        ('k', 0b00001001),
+        ('a', 0b00000011),
+        ('i', 0b00010001),
+        ('n', 0b00100010),
+    ],
+    &[
+        ('c', 0b00101010),
+        ('e', 0b00010101),
+        ('k', 0b00100101),
+        ('a', 0b00000111),
+        ('i', 0b10000011),
+        ('n', 0b00001011),
+        ('y', 0b00101001),
+        ('q', 0b00100011),
+        ('j', 0b01000011),
+        ('r', 0b00010011),
+    ],
+    &[
+        ('c', 0b10101010),
+        ('e', 0b01010101),
+        ('k', 0b01001011),
+        ('a', 0b00001111),
+        ('i', 0b00011011),
+        ('n', 0b10001011),
+        ('y', 0b00101011),
+        ('q', 0b00100111),
+        ('j', 0b01010011),
+        ('r', 0b00010111),
+        ('t', 0b00111001),
+        ('w', 0b01100011),
+        ('z', 0b00110011),
+    ],
+];
+
+impl Rule {
+    /// Fails for rules read as bytes with a number of states [`Rule::from_str`] refuses
+    pub fn check(&self) -> Result<()> {
+        if !(2..=MAX_STATES).contains(&self.states) {
+            bail!(
+                "Expected a number of states from 2 to {MAX_STATES}, found {}",
+                self.states
+            );
+        }
+        Ok(())
+    }
+
+    /// Number of states, two for Life-like rules
+    pub fn states(&self) -> u32 {
+        self.states
+    }
+
+    pub fn born(&self, neighborhood: u8) -> bool {
+        get_bit(&self.birth, neighborhood)
+    }
+
+    pub fn survives(&self, neighborhood: u8) -> bool {
+        get_bit(&self.survival, neighborhood)
+    }
+
+    /// State of a cell in the next generation, given its state and its alive neighbors
+    pub fn next_state(&self, state: u32, neighborhood: u8) -> u32 {
+        match state {
+            0 => self.born(neighborhood) as u32,
+            1 if self.survives(neighborhood) => 1,
+            // Failing to survive starts dying, and the last dying state dies
+            _ => (state + 1) % self.states,
+        }
+    }
+}
+
+impl Default for Rule {
+    /// Conway's Game of Life
+    fn default() -> Self {
+        "B3/S23".parse().unwrap()
+    }
+}
+
+fn get_bit(table: &[u32; 8], neighborhood: u8) -> bool {
+    table[neighborhood as usize / 32] >> (neighborhood % 32) & 1 == 1
+}
+
+fn set_bit(table: &mut [u32; 8], neighborhood: u8) {
+    table[neighborhood as usize / 32] |= 1 << (neighborhood % 32);
+}
+
+/// All rotations and reflections of a neighborhood
+fn symmetries(neighborhood: u8) -> impl Iterator<Item = u8> {
+    let reflected = (0..8).fold(0u8, |acc, i| {
+        acc | ((neighborhood >> i) & 1) << ((8 - i) % 8)
+    });
+    // Rotating by two neighbors turns the neighborhood by 90 degrees
+    (0..4).flat_map(move |quarter| {
+        [
+            neighborhood.rotate_left(2 * quarter),
+            reflected.rotate_left(2 * quarter),
+        ]
+    })
+}
+
+/// Letters of Hensel notation valid for the given neighbor count, with their neighborhoods
+fn hensel_letters(count: u32) -> impl Iterator<Item = (char, u8)> {
+    let complement = count > 4;
+    let letters = HENSEL_LETTERS[if complement { 8 - count } else { count } as usize];
+    letters
+        .iter()
+        .map(move |&(letter, neighborhood)| match complement {
+            true => (letter, !neighborhood),
+            false => (letter, neighborhood),
+        })
+}
+
+/// Parses the conditions for birth or survival, e.g. `23` or `2-a3ij`
+fn parse_conditions(conditions: &str) -> Result<[u32; 8]> {
+    let mut table = [0; 8];
+    let mut chars = conditions.chars().peekable();
+    while let Some(c) = chars.next() {
+        let count = c
+            .to_digit(10)
+            .filter(|&count| count <= 8)
+            .ok_or_else(|| anyhow!("Expected a neighbor count from 0 to 8, found '{c}'"))?;
+        let negated = chars.next_if_eq(&'-').is_some();
+        let mut letters = Vec::new();
+        while let Some(letter) = chars.next_if(char::is_ascii_alphabetic) {
+            letters.push(letter.to_ascii_lowercase());
+        }
+        if negated && letters.is_empty() {
+            bail!("Expected letters after '{count}-'");
+        }
+        for &letter in &letters {
+            if !hensel_letters(count).any(|(valid, _)| valid == letter) {
+                bail!("'{letter}' is not a valid Hensel letter for {count} neighbors");
+            }
+        }
+
+        // Without letters every neighborhood with this many neighbors is included
+        let included = |letter| letters.is_empty() || letters.contains(&letter) != negated;
+        for (_, neighborhood) in hensel_letters(count).filter(|&(letter, _)| included(letter)) {
+            symmetries(neighborhood).for_each(|n| set_bit(&mut table, n));
+        }
+        if count == 0 || count == 8 {
+            set_bit(&mut table, if count == 0 { 0 } else { u8::MAX });
+        }
+    }
+    Ok(table)
+}
+
+/// Writes the conditions in Hensel notation, choosing the shorter of listing or excluding letters
+fn format_conditions(table: &[u32; 8]) -> String {
+    let mut conditions = String::new();
+    for count in 0..=8 {
+        if count == 0 || count == 8 {
+            if get_bit(table, if count == 0 { 0 } else { u8::MAX }) {
+                conditions += &count.to_string();
+            }
+            continue;
+        }
+        let (included, excluded): (Vec<_>, Vec<_>) =
+            hensel_letters(count).partition(|&(_, neighborhood)| get_bit(table, neighborhood));
+        let letters = |letters: Vec<(char, u8)>| -> String {
+            letters.into_iter().map(|(letter, _)| letter).collect()
+        };
+        if included.is_empty() {
+            continue;
+        }
+        conditions += &count.to_string();
+        if excluded.is_empty() {
+            continue;
+        }
+        conditions += &if excluded.len() < included.len() {
+            format!("-{}", letters(excluded))
+        } else {
+            letters(included)
+        };
+    }
+    conditions
+}
+
+/// Splits off the number of states of a Generations rule,
+/// either a third section like `345/2/4` or a final section starting with C or G like `B2/S/C3`
+fn split_states(rulestring: &str) -> Result<(&str, u32)> {
+    let Some((rest, last)) = rulestring.rsplit_once('/') else {
+        return Ok((rulestring, 2));
+    };
+    let prefixed = last.strip_prefix(['C', 'c', 'G', 'g']);
+    if prefixed.is_none() && !rest.contains('/') {
+        return Ok((rulestring, 2));
+    }
+    let states = prefixed.unwrap_or(last);
+    let states = states
+        .parse()
+        .ok()
+        .filter(|states| (2..=MAX_STATES).contains(states))
+        .ok_or_else(|| {
+            anyhow!("Expected a number of states from 2 to {MAX_STATES}, found '{states}'")
+        })?;
+    Ok((rest, states))
+}
+
+impl FromStr for Rule {
+    type Err = anyhow::Error;
+
+    /// Accepts `B3/S23` style notation (the slash is optional, B and S in either order),
+    /// the older survival/birth `23/3` notation, and Hensel letters after any count.
+    /// Generations rules add their number of states, as in `B2/S/C3` or `345/2/4`
+    fn from_str(rulestring: &str) -> Result<Self> {
+        let parse = || -> Result<Self> {
+            let (rulestring, states) = split_states(rulestring.trim())?;
+            let starts_with_letter = rulestring
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic());
+            let (birth, survival) = if starts_with_letter {
+                let mut birth = None;
+                let mut survival = None;
+                // Split into sections at every B or S, the slash between them is optional
+                let mut rest = rulestring;
+                while let Some(section) = rest.chars().next() {
+                    let end = rest[1..]
+                        .find(['b', 'B', 's', 'S'])
+                        .map_or(rest.len(), |i| i + 1);
+                    let conditions = rest[1..end].trim_end_matches('/');
+                    let slot = match section.to_ascii_uppercase() {
+                        'B' => &mut birth,
+                        'S' => &mut survival,
+                        _ => bail!("Expected 'B' or 'S', found '{section}'"),
+                    };
+                    if slot.replace(conditions).is_some() {
+                        bail!("'{}' appears more than once", section.to_ascii_uppercase());
+                    }
+                    rest = &rest[end..];
+                }
+                (birth.unwrap_or_default(), survival.unwrap_or_default())
+            } else {
+                let (survival, birth) = rulestring
+                    .split_once('/')
+                    .ok_or_else(|| anyhow!("Expected survival/birth separated by '/'"))?;
+                (birth, survival)
+            };
+
+            Ok(Self {
+                birth: parse_conditions(birth).context("Invalid birth conditions")?,
+                survival: parse_conditions(survival).context("Invalid survival conditions")?,
+                states,
+                _padding: [0; 3],
+            })
+        };
+        parse().with_context(|| format!("Failed to parse rule '{rulestring}'"))
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "B{}/S{}",
+            format_conditions(&self.birth),
+            format_conditions(&self.survival)
+        )?;
+        if self.states > 2 {
+            write!(f, "/C{}", self.states)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(rulestring: &str) -> Rule {
+        rulestring.parse().unwrap()
+    }
+
+    fn error(rulestring: &str) -> String {
+        format!("{:#}", rulestring.parse::<Rule>().unwrap_err())
+    }
+
+    /// Every neighborhood with `count` alive neighbors
+    fn with_count(count: u32) -> impl Iterator<Item = u8> {
+        (0..=u8::MAX).filter(move |neighborhood| neighborhood.count_ones() == count)
+    }
+
+    #[test]
+    fn parses_conway() {
+        let conway = rule("B3/S23");
+        for count in 0..=8 {
+            assert!(with_count(count).all(|n| conway.born(n) == (count == 3)));
+            assert!(with_count(count).all(|n| conway.survives(n) == (2..=3).contains(&count)));
+        }
+        assert_eq!(conway, Rule::default());
+        for equivalent in ["23/3", "S23/B3", "B3S23", "s23b3", " b3/s23 "] {
+            assert_eq!(rule(equivalent), conway, "{equivalent}");
+        }
+    }
+
+    #[test]
+    fn parses_highlife() {
+        let highlife = rule("B36/S23");
+        assert!(with_count(6).all(|n| highlife.born(n)));
+        assert!(with_count(3).all(|n| highlife.born(n)));
+        assert!(!with_count(4).any(|n| highlife.born(n)));
+        assert_eq!(rule("23/36"), highlife);
+    }
+
+    #[test]
+    fn parses_hensel_letters() {
+        let rule = rule("B2-a/S12");
+        // Adjacent pairs in every rotation and reflection
+        for adjacent in [0b00000011, 0b00000110, 0b11000000, 0b10000001] {
+            assert!(!rule.born(adjacent));
+        }
+        // Two corners, two edges, a knight's move and opposite neighbors
+        for other in [0b00001010, 0b00000101, 0b00001001, 0b00010001, 0b00100010] {
+            assert!(rule.born(other));
+        }
+        assert!(with_count(1).all(|n| rule.survives(n)));
+        assert!(with_count(2).all(|n| rule.survives(n)));
+        assert!(!with_count(3).any(|n| rule.survives(n) || rule.born(n)));
+    }
+
+    #[test]
+    fn parses_negated_and_listed_letters() {
+        let rule = rule("B3-cnq/S2ae3aeijr");
+        let [c, e, k, a, i, n, y, q, j, r] = [
+            0b00101010, 0b00010101, 0b00100101, 0b00000111, 0b10000011, 0b00001011, 0b00101001,
+            0b00100011, 0b01000011, 0b00010011,
+        ];
+        for (neighborhood, born) in [
+            (c, false),
+            (e, true),
+            (k, true),
+            (a, true),
+            (i, true),
+            (n, false),
+            (y, true),
+            (q, false),
+            (j, true),
+            (r, true),
+        ] {
+            assert_eq!(rule.born(neighborhood), born, "{neighborhood:08b}");
+            // Turned by 90 degrees
+            assert_eq!(rule.born(neighborhood.rotate_left(2)), born);
+        }
+        for (neighborhood, survives) in [
+            (a, true),
+            (e, true),
+            (i, true),
+            (j, true),
+            (r, true),
+            (c, false),
+            (k, false),
+            (n, false),
+            (y, false),
+            (q, false),
+        ] {
+            assert_eq!(rule.survives(neighborhood), survives, "{neighborhood:08b}");
+        }
+        assert!(rule.survives(0b00000101) && rule.survives(0b00000011));
+        assert!(!rule.survives(0b00001010) && !rule.survives(0b00010001));
+    }
+
+    #[test]
+    fn complements_letters_above_four() {
+        // 6i is the complement of 2i: every neighbor but north and south
+        let rule = rule("B6i/S");
+        assert!(rule.born(!0b00010001));
+        assert!(!rule.born(!0b00000011));
+        assert!(rule.born(!0b01000100));
+    }
+
+    #[test]
+    fn parses_generations() {
+        let brians_brain = rule("B2/S/C3");
+        assert_eq!(rule("/2/3"), brians_brain);
+        assert_eq!(brians_brain.next_state(0, 0b00000101), 1);
+        assert_eq!(brians_brain.next_state(1, 0b00000101), 2);
+        assert_eq!(brians_brain.next_state(2, 0b00000101), 0);
+        let star_wars = rule("345/2/4");
+        assert_eq!(star_wars, rule("B2/S345/G4"));
+        assert_eq!(star_wars.next_state(1, 0b00000111), 1);
+        assert_eq!(star_wars.next_state(1, 0b00000001), 2);
+        assert_eq!(star_wars.next_state(3, 0b00000011), 0);
+    }
+
+    #[test]
+    fn displays_round_trip() {
+        for (rulestring, displayed) in [
+            ("23/3", "B3/S23"),
+            ("B36/S23", "B36/S23"),
+            ("B2-a/S12", "B2-a/S12"),
+            ("B3-cnq/S2ae3aeijr", "B3-cnq/S2ea3eaijr"),
+            ("B0123478/S34678", "B0123478/S34678"),
+            ("B2/S/C3", "B2/S/C3"),
+            ("345/2/4", "B2/S345/C4"),
+            ("B3/S2-c3-k4eity", "B3/S2-c3-k4eiyt"),
+        ] {
+            let parsed = rule(rulestring);
+            assert_eq!(parsed.to_string(), displayed);
+            assert_eq!(rule(displayed), parsed);
+        }
+    }
+
+    #[test]
+    fn reports_errors() {
+        assert_eq!(
+            error("B9/S23"),
+            "Failed to parse rule 'B9/S23': Invalid birth conditions: \
+            Expected a neighbor count from 0 to 8, found '9'"
+        );
+        assert_eq!(
+            error("B3z/S23"),
+            "Failed to parse rule 'B3z/S23': Invalid birth conditions: \
+            'z' is not a valid Hensel letter for 3 neighbors"
+        );
+        assert_eq!(
+            error("B3/S23B6"),
+            "Failed to parse rule 'B3/S23B6': 'B' appears more than once"
+        );
+        assert_eq!(
+            error("23"),
+            "Failed to parse rule '23': Expected survival/birth separated by '/'"
+        );
+        assert_eq!(
+            error("B2/S/C1"),
+            "Failed to parse rule 'B2/S/C1': Expected a number of states from 2 to 256, found '1'"
+        );
+        assert_eq!(
+            error("B2-/S"),
+            "Failed to parse rule 'B2-/S': Invalid birth conditions: Expected letters after '2-'"
+        );
+    }
+}
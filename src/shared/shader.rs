@@ -1,18 +1,27 @@
-use anyhow::Result;
-
 pub struct Shader {
     pub module: wgpu::ShaderModule,
 }
 
+/// Declarations of `SimulationParams`, listed first by every shader.
+/// Sources are embedded in the binary so it runs from any working directory
+pub const SIM_PARAMS: &str = include_str!("sim_params.wgsl");
+
+/// Reading cells beyond the edges of the grid, listed after [`SIM_PARAMS`] by compute shaders
+pub const BOUNDARY: &str = include_str!("boundary.wgsl");
+
 impl Shader {
-    pub fn new(source: &str, device: &wgpu::Device) -> Result<Self> {
-        let shader_str = std::fs::read_to_string(source)?;
+    /// Compiles the concatenation of the given sources,
+    /// so shared declarations such as [`SIM_PARAMS`] can be listed first
+    pub fn new(label: &str, sources: &[&str], device: &wgpu::Device) -> Self {
+        Self::from_source(&sources.concat(), Some(label), device)
+    }
 
+    pub fn from_source(shader_str: &str, label: Option<&str>, device: &wgpu::Device) -> Self {
         let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
+            label,
             source: wgpu::ShaderSource::Wgsl(shader_str.into()),
         });
 
-        Ok(Self { module })
+        Self { module }
     }
 }
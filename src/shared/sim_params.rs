@@ -1,3 +1,6 @@
+use super::{boundary::Boundary, larger_than_life::LargerThanLifeRule, rule::Rule};
+use anyhow::{bail, Context};
+use std::str::FromStr;
 use wgpu::util::DeviceExt;
 
 pub struct SimulationParamsBuf {
@@ -5,11 +8,174 @@ pub struct SimulationParamsBuf {
     pub params_buf: wgpu::Buffer,
 }
 
+/// Laid out to match `sim_params.wgsl`:
+/// nested structs start on 16 byte boundaries in uniform buffers
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct SimulationParams {
-    width: u32,
-    height: u32,
+    pub width: u32,
+    pub height: u32,
+    mode: u32,
+    boundary: u32,
+    pub rule: Rule,
+    pub physarum: PhysarumParams,
+    pub gray_scott: GrayScottParams,
+    pub lenia: LeniaParams,
+    pub smooth_life: SmoothLifeParams,
+    pub larger_than_life: LargerThanLifeRule,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SimulationMode {
+    /// Life-like cellular automaton following `SimulationParams::rule`
+    #[default]
+    Life,
+    /// Slime mold agents following and depositing into a diffusing trail map
+    Physarum,
+    /// Two chemical reaction-diffusion, with U and V concentrations in the red and green channels
+    GrayScott,
+    /// Continuous automaton convolving the state with a ring-shaped kernel
+    Lenia,
+    /// Rafler's SmoothLife, Life generalised to the filling of an inner disk and outer annulus
+    SmoothLife,
+    /// Evans' Larger than Life, following `SimulationParams::larger_than_life`
+    LargerThanLife,
+}
+
+impl SimulationMode {
+    /// Every mode, indexed by the discriminant stored in the uniform
+    const ALL: [Self; 6] = [
+        Self::Life,
+        Self::Physarum,
+        Self::GrayScott,
+        Self::Lenia,
+        Self::SmoothLife,
+        Self::LargerThanLife,
+    ];
+
+    /// Format of the ping-pong textures holding the simulation state
+    pub fn texture_format(&self, adapter: &wgpu::Adapter) -> wgpu::TextureFormat {
+        match self {
+            Self::Life | Self::Physarum | Self::Lenia | Self::SmoothLife | Self::LargerThanLife => {
+                wgpu::TextureFormat::R32Float
+            }
+            // Downlevel adapters can't store two channel textures, pad them to four
+            Self::GrayScott => [
+                wgpu::TextureFormat::Rg32Float,
+                wgpu::TextureFormat::Rgba32Float,
+            ]
+            .into_iter()
+            .find(|&format| {
+                adapter
+                    .get_texture_format_features(format)
+                    .allowed_usages
+                    .contains(wgpu::TextureUsages::STORAGE_BINDING)
+            })
+            .unwrap_or(wgpu::TextureFormat::Rgba32Float),
+        }
+    }
+
+    /// Leading channels of a cell painted alive or cleared by hand
+    pub fn painted_cell(&self, alive: bool) -> [f32; 2] {
+        match (self, alive) {
+            // Clearing restores the trivial state, painting seeds it like Pearson's square
+            (Self::GrayScott, true) => [0.5, 0.25],
+            (Self::GrayScott, false) => [1.0, 0.0],
+            (_, alive) => [alive as u8 as f32, 0.0],
+        }
+    }
+
+    /// Leading channels of a cell `value` of the way from cleared to painted alive,
+    /// which are 0 and 1 outside Gray-Scott
+    pub fn blended_cell(&self, value: f32) -> [f32; 2] {
+        let (dead, live) = (self.painted_cell(false), self.painted_cell(true));
+        [0, 1].map(|channel| dead[channel] + (live[channel] - dead[channel]) * value)
+    }
+
+    /// Leading channels of a cell in a numbered state of a pattern file.
+    /// Life and Larger than Life keep the number, other modes only tell dead from alive
+    pub fn pattern_cell(&self, state: u8) -> [f32; 2] {
+        match self {
+            Self::Life | Self::LargerThanLife => [state as f32, 0.0],
+            _ => self.painted_cell(state > 0),
+        }
+    }
+
+    /// Numbered state of a texel for pattern files, the inverse of [`SimulationMode::pattern_cell`]
+    pub fn cell_state(&self, texel: &[f32]) -> u8 {
+        match self {
+            Self::Life | Self::LargerThanLife => texel[0].round().clamp(0.0, u8::MAX as f32) as u8,
+            // V is a quarter in painted cells and zero in the trivial state
+            Self::GrayScott => (texel[1] >= 0.125) as u8,
+            _ => (texel[0] >= 0.5) as u8,
+        }
+    }
+}
+
+/// Angles in radians, distances in cells
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PhysarumParams {
+    pub sensor_angle: f32,
+    pub sensor_distance: f32,
+    pub turn_speed: f32,
+    pub move_speed: f32,
+    /// Trail added by each agent per step
+    pub deposit: f32,
+    /// Fraction of the trail lost per step
+    pub decay: f32,
+    /// Blend between the trail and its 3x3 mean per step
+    pub diffuse: f32,
+    pub agent_count: u32,
+}
+
+/// Rates per step, diffusion coefficients in cells squared per step.
+/// U is fed in at `feed`, V is removed at `feed + kill`, and U + 2V -> 3V
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GrayScottParams {
+    pub feed: f32,
+    pub kill: f32,
+    pub diffusion_u: f32,
+    pub diffusion_v: f32,
+    pub dt: f32,
+    _padding: [u32; 3],
+}
+
+/// The kernel is `radius` cells wide and made of concentric shells with heights `peaks`,
+/// the growth function is a Gaussian bump centred on `mu` mapped to [-1, 1]
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LeniaParams {
+    pub radius: u32,
+    pub mu: f32,
+    pub sigma: f32,
+    /// Reciprocal of the number of steps per unit of time
+    pub dt: f32,
+    pub peaks: [f32; 4],
+    /// Number of `peaks` in use
+    pub peak_count: u32,
+    _padding: [u32; 3],
+}
+
+/// Radii in cells, intervals and sigmoid widths in units of filling.
+/// Cells are born when the annulus filling is within `birth` and survive within `death`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SmoothLifeParams {
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+    pub birth: [f32; 2],
+    pub death: [f32; 2],
+    /// Width of the sigmoid over the annulus filling
+    pub alpha_n: f32,
+    /// Width of the sigmoid between birth and death over the disk filling
+    pub alpha_m: f32,
+    /// Time step of the time-continuous variant
+    pub dt: f32,
+    /// Nonzero to step by `dt` towards the transition function instead of replacing the state
+    pub continuous: u32,
+    _padding: [u32; 2],
 }
 
 impl SimulationParamsBuf {
@@ -25,10 +191,463 @@ impl SimulationParamsBuf {
 }
 
 impl SimulationParams {
-    pub fn new(size: &winit::dpi::PhysicalSize<u32>) -> Self {
+    /// `size` is the size of the grid in cells, independent of the window
+    pub fn new(size: &winit::dpi::PhysicalSize<u32>, mode: SimulationMode) -> Self {
         Self {
             width: size.width,
             height: size.height,
+            mode: mode as u32,
+            boundary: Boundary::default() as u32,
+            rule: Rule::default(),
+            physarum: PhysarumParams::default(),
+            gray_scott: GrayScottParams::default(),
+            lenia: LeniaParams::default(),
+            smooth_life: SmoothLifeParams::default(),
+            larger_than_life: LargerThanLifeRule::default(),
+        }
+    }
+
+    /// Every field is a 4 byte word, saved little endian so files move between machines
+    pub fn to_bytes(self) -> Vec<u8> {
+        bytemuck::cast_slice::<Self, u32>(std::slice::from_ref(&self))
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect()
+    }
+
+    /// Parameters saved by [`SimulationParams::to_bytes`], checking the fields indexing enums,
+    /// the grid size and the rules
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.len() != std::mem::size_of::<Self>() {
+            bail!(
+                "Expected {} bytes of parameters, found {}",
+                std::mem::size_of::<Self>(),
+                bytes.len()
+            );
+        }
+        let words: Vec<u32> = bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        let params: Self = bytemuck::pod_read_unaligned(bytemuck::cast_slice(&words));
+        if params.mode as usize >= SimulationMode::ALL.len() {
+            bail!("Unknown simulation mode {}", params.mode);
+        }
+        if params.boundary as usize >= Boundary::ALL.len() {
+            bail!("Unknown boundary {}", params.boundary);
+        }
+        if params.width == 0 || params.height == 0 {
+            bail!("Grid must be at least one cell wide and high");
+        }
+        params.rule.check().context("Invalid rule")?;
+        params
+            .larger_than_life
+            .check()
+            .context("Invalid Larger than Life rule")?;
+        params
+            .physarum
+            .check()
+            .context("Invalid Physarum parameters")?;
+        params
+            .gray_scott
+            .check()
+            .context("Invalid Gray-Scott parameters")?;
+        params.lenia.check().context("Invalid Lenia parameters")?;
+        params
+            .smooth_life
+            .check()
+            .context("Invalid SmoothLife parameters")?;
+        Ok(params)
+    }
+
+    pub fn mode(&self) -> SimulationMode {
+        SimulationMode::ALL[self.mode as usize]
+    }
+
+    pub fn boundary(&self) -> Boundary {
+        Boundary::ALL[self.boundary as usize]
+    }
+
+    pub fn set_boundary(&mut self, boundary: Boundary) {
+        self.boundary = boundary as u32;
+    }
+
+    /// Parses the rule of Life or Larger than Life, whichever the mode is
+    pub fn set_rule(&mut self, rule: &str) -> anyhow::Result<()> {
+        match self.mode() {
+            SimulationMode::Life => self.rule = rule.parse()?,
+            SimulationMode::LargerThanLife => self.larger_than_life = rule.parse()?,
+            mode => bail!("{mode:?} mode does not take a rule"),
+        }
+        Ok(())
+    }
+
+    /// Cell value exported as white: the last numbered state of Life and Larger than Life,
+    /// so every dying state of Generations rules gets its own shade, and 1 in other modes
+    pub fn white_value(&self) -> f32 {
+        match self.mode() {
+            SimulationMode::Life => (self.rule.states() - 1) as f32,
+            SimulationMode::LargerThanLife => (self.larger_than_life.states() - 1) as f32,
+            _ => 1.0,
+        }
+    }
+
+    /// The rule in the notation [`SimulationParams::set_rule`] parses, in modes that have one
+    pub fn rule_string(&self) -> Option<String> {
+        match self.mode() {
+            SimulationMode::Life => Some(self.rule.to_string()),
+            SimulationMode::LargerThanLife => Some(self.larger_than_life.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Fails naming the first of `values` that is infinite or NaN
+fn check_finite(values: &[(&str, f32)]) -> anyhow::Result<()> {
+    match values.iter().find(|(_, value)| !value.is_finite()) {
+        Some((name, value)) => bail!("Expected a finite {name}, found {value}"),
+        None => Ok(()),
+    }
+}
+
+impl PhysarumParams {
+    pub fn check(&self) -> anyhow::Result<()> {
+        if self.agent_count == 0 {
+            bail!("Physarum needs at least one agent, found 0");
+        }
+        check_finite(&[
+            ("sensor angle", self.sensor_angle),
+            ("sensor distance", self.sensor_distance),
+            ("turn speed", self.turn_speed),
+            ("move speed", self.move_speed),
+            ("deposit", self.deposit),
+            ("decay", self.decay),
+            ("diffuse", self.diffuse),
+        ])
+    }
+}
+
+impl Default for PhysarumParams {
+    fn default() -> Self {
+        Self {
+            sensor_angle: 22.5_f32.to_radians(),
+            sensor_distance: 9.0,
+            turn_speed: 45.0_f32.to_radians(),
+            move_speed: 1.0,
+            deposit: 0.1,
+            decay: 0.1,
+            diffuse: 1.0,
+            agent_count: 1 << 18,
+        }
+    }
+}
+
+impl Default for GrayScottParams {
+    /// Pearson's diffusion coefficients of 2e-5 and 1e-5 on his 2.5 by 2.5 domain of 256 cells,
+    /// with feed and kill rates that grow coral-like stripes
+    fn default() -> Self {
+        let cell_size = 2.5 / 256.0;
+        Self {
+            feed: 0.055,
+            kill: 0.062,
+            diffusion_u: 2e-5 / (cell_size * cell_size),
+            diffusion_v: 1e-5 / (cell_size * cell_size),
+            dt: 1.0,
+            _padding: [0; 3],
+        }
+    }
+}
+
+impl GrayScottParams {
+    pub fn check(&self) -> anyhow::Result<()> {
+        check_finite(&[
+            ("feed rate", self.feed),
+            ("kill rate", self.kill),
+            ("U diffusion", self.diffusion_u),
+            ("V diffusion", self.diffusion_v),
+            ("time step", self.dt),
+        ])
+    }
+}
+
+impl LeniaParams {
+    /// Every cell sums `(2 * radius + 1)^2` weighted neighbours each step, so larger kernels
+    /// would stall the GPU long before they run out of memory
+    pub const MAX_RADIUS: u32 = 1024;
+
+    pub fn new(radius: u32, mu: f32, sigma: f32, dt: f32, peaks: &[f32]) -> anyhow::Result<Self> {
+        if peaks.is_empty() || peaks.len() > 4 {
+            bail!(
+                "Lenia kernels have one to four peaks, found {}",
+                peaks.len()
+            );
+        }
+        let mut padded_peaks = [0.0; 4];
+        padded_peaks[..peaks.len()].copy_from_slice(peaks);
+        let params = Self {
+            radius,
+            mu,
+            sigma,
+            dt,
+            peaks: padded_peaks,
+            peak_count: peaks.len() as u32,
+            _padding: [0; 3],
+        };
+        params.check()?;
+        Ok(params)
+    }
+
+    /// Checks the fields [`LeniaParams::new`] takes, and the peak count it derives
+    pub fn check(&self) -> anyhow::Result<()> {
+        if !(1..=Self::MAX_RADIUS).contains(&self.radius) {
+            bail!(
+                "Lenia kernel radius must be 1 to {} cells, found {}",
+                Self::MAX_RADIUS,
+                self.radius
+            );
+        }
+        if !(1..=4).contains(&self.peak_count) {
+            bail!(
+                "Lenia kernels have one to four peaks, found {}",
+                self.peak_count
+            );
+        }
+        check_finite(&[
+            ("mu", self.mu),
+            ("sigma", self.sigma),
+            ("time step", self.dt),
+        ])?;
+        for &peak in self.peaks() {
+            check_finite(&[("peak", peak)])?;
+        }
+        let total: f32 = self.peaks().iter().sum();
+        if total <= 0.0 {
+            bail!(
+                "Lenia kernel peaks {:?} must have a positive total",
+                self.peaks()
+            );
+        }
+        Ok(())
+    }
+
+    /// Active peaks of the kernel shells, from the centre outwards
+    pub fn peaks(&self) -> &[f32] {
+        &self.peaks[..self.peak_count as usize]
+    }
+}
+
+impl Default for LeniaParams {
+    /// Parameters of Orbium, the glider of Lenia
+    fn default() -> Self {
+        Self::new(13, 0.15, 0.015, 0.1, &[1.0]).unwrap()
+    }
+}
+
+impl SmoothLifeParams {
+    /// Every cell integrates the square around the outer radius each step, as in Lenia
+    pub const MAX_RADIUS: f32 = LeniaParams::MAX_RADIUS as f32;
+
+    pub fn check(&self) -> anyhow::Result<()> {
+        check_finite(&[
+            ("lower birth bound", self.birth[0]),
+            ("upper birth bound", self.birth[1]),
+            ("lower death bound", self.death[0]),
+            ("upper death bound", self.death[1]),
+            ("alpha_n", self.alpha_n),
+            ("alpha_m", self.alpha_m),
+            ("time step", self.dt),
+        ])?;
+        // Rafler's squares are as wide as the outer radius, which must round to at least one cell
+        if !(0.0 < self.inner_radius
+            && self.inner_radius < self.outer_radius
+            && (1.0..=Self::MAX_RADIUS).contains(&self.outer_radius))
+        {
+            bail!(
+                "SmoothLife radii must satisfy 0 < inner < outer and 1 <= outer <= {}, \
+                found {} and {}",
+                Self::MAX_RADIUS,
+                self.inner_radius,
+                self.outer_radius
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Default for SmoothLifeParams {
+    /// Rafler's parameters, time-continuous since random starts die out in the discrete variant
+    fn default() -> Self {
+        Self {
+            inner_radius: 7.0,
+            outer_radius: 21.0,
+            birth: [0.278, 0.365],
+            death: [0.267, 0.445],
+            alpha_n: 0.028,
+            alpha_m: 0.147,
+            dt: 0.1,
+            continuous: 1,
+            _padding: [0; 2],
+        }
+    }
+}
+
+impl FromStr for SimulationMode {
+    type Err = anyhow::Error;
+
+    fn from_str(mode: &str) -> anyhow::Result<Self> {
+        Ok(match mode.to_ascii_lowercase().as_str() {
+            "life" => Self::Life,
+            "physarum" => Self::Physarum,
+            "gray-scott" | "grayscott" => Self::GrayScott,
+            "lenia" => Self::Lenia,
+            "smoothlife" | "smooth-life" => Self::SmoothLife,
+            "larger-than-life" | "ltl" => Self::LargerThanLife,
+            _ => bail!(
+                "Unknown simulation mode '{mode}', \
+                expected life, physarum, gray-scott, lenia, smooth-life or larger-than-life"
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::offset_of;
+
+    fn params() -> SimulationParams {
+        let mut params = SimulationParams::new(
+            &winit::dpi::PhysicalSize::new(640, 360),
+            SimulationMode::LargerThanLife,
+        );
+        params.set_rule("R7,C5,M1,S10..20,B12..14,NC").unwrap();
+        params
+    }
+
+    /// The saved parameters with the word at byte `offset` replaced
+    fn with_word(offset: usize, word: u32) -> Vec<u8> {
+        let mut bytes = params().to_bytes();
+        bytes[offset..offset + 4].copy_from_slice(&word.to_le_bytes());
+        bytes
+    }
+
+    fn error(bytes: &[u8]) -> String {
+        format!("{:#}", SimulationParams::from_bytes(bytes).unwrap_err())
+    }
+
+    #[test]
+    fn round_trips_bytes_little_endian() {
+        let bytes = params().to_bytes();
+        assert_eq!(bytes[..8], [128, 2, 0, 0, 104, 1, 0, 0]);
+        let read = SimulationParams::from_bytes(&bytes).unwrap();
+        assert_eq!(read.to_bytes(), bytes);
+        assert_eq!(read.mode(), SimulationMode::LargerThanLife);
+        assert_eq!(
+            read.rule_string().as_deref(),
+            Some("R7,C5,M1,S10..20,B12..14,NC")
+        );
+    }
+
+    #[test]
+    fn refuses_states_out_of_bounds() {
+        // Rules start with their tables of 8 words each, then Life's number of states
+        let rule_states = offset_of!(SimulationParams, rule) + 64;
+        // Larger than Life rules start with their range, then their number of states
+        let larger_states = offset_of!(SimulationParams, larger_than_life) + 4;
+        for states in [0, 1, 257, u32::MAX] {
+            assert_eq!(
+                error(&with_word(rule_states, states)),
+                format!("Invalid rule: Expected a number of states from 2 to 256, found {states}")
+            );
+            assert_eq!(
+                error(&with_word(larger_states, states)),
+                format!("Invalid Larger than Life rule: Expected 2 to 256 states, found {states}")
+            );
+        }
+        assert!(SimulationParams::from_bytes(&with_word(rule_states, 256)).is_ok());
+        assert!(SimulationParams::from_bytes(&with_word(larger_states, 256)).is_ok());
+    }
+
+    #[test]
+    fn refuses_lenia_kernels_out_of_range() {
+        let lenia = offset_of!(SimulationParams, lenia);
+        let peak_count = lenia + offset_of!(LeniaParams, peak_count);
+        for count in [0, 5, u32::MAX] {
+            assert_eq!(
+                error(&with_word(peak_count, count)),
+                format!(
+                    "Invalid Lenia parameters: Lenia kernels have one to four peaks, found {count}"
+                )
+            );
+        }
+        let radius = lenia + offset_of!(LeniaParams, radius);
+        for radius_cells in [0, LeniaParams::MAX_RADIUS + 1, 1 << 16, u32::MAX] {
+            assert_eq!(
+                error(&with_word(radius, radius_cells)),
+                format!(
+                    "Invalid Lenia parameters: \
+                    Lenia kernel radius must be 1 to 1024 cells, found {radius_cells}"
+                )
+            );
+        }
+        assert!(SimulationParams::from_bytes(&with_word(radius, LeniaParams::MAX_RADIUS)).is_ok());
+        let first_peak = lenia + offset_of!(LeniaParams, peaks);
+        assert_eq!(
+            error(&with_word(first_peak, (-1.0_f32).to_bits())),
+            "Invalid Lenia parameters: Lenia kernel peaks [-1.0] must have a positive total"
+        );
+        assert_eq!(
+            error(&with_word(first_peak, f32::INFINITY.to_bits())),
+            "Invalid Lenia parameters: Expected a finite peak, found inf"
+        );
+        assert!(LeniaParams::new(13, 0.15, 0.015, 0.1, &[0.5, -0.5]).is_err());
+    }
+
+    #[test]
+    fn refuses_non_finite_parameters() {
+        let nan = f32::NAN.to_bits();
+        let physarum = offset_of!(SimulationParams, physarum);
+        assert_eq!(
+            error(&with_word(
+                physarum + offset_of!(PhysarumParams, agent_count),
+                0
+            )),
+            "Invalid Physarum parameters: Physarum needs at least one agent, found 0"
+        );
+        assert_eq!(
+            error(&with_word(
+                physarum + offset_of!(PhysarumParams, decay),
+                nan
+            )),
+            "Invalid Physarum parameters: Expected a finite decay, found NaN"
+        );
+        let gray_scott = offset_of!(SimulationParams, gray_scott);
+        assert_eq!(
+            error(&with_word(
+                gray_scott + offset_of!(GrayScottParams, diffusion_v),
+                f32::NEG_INFINITY.to_bits()
+            )),
+            "Invalid Gray-Scott parameters: Expected a finite V diffusion, found -inf"
+        );
+        let smooth_life = offset_of!(SimulationParams, smooth_life);
+        assert_eq!(
+            error(&with_word(
+                smooth_life + offset_of!(SmoothLifeParams, dt),
+                nan
+            )),
+            "Invalid SmoothLife parameters: Expected a finite time step, found NaN"
+        );
+        let outer_radius = smooth_life + offset_of!(SmoothLifeParams, outer_radius);
+        for radius in [f32::INFINITY, SmoothLifeParams::MAX_RADIUS + 1.0, 1e30] {
+            assert_eq!(
+                error(&with_word(outer_radius, radius.to_bits())),
+                format!(
+                    "Invalid SmoothLife parameters: SmoothLife radii must satisfy \
+                    0 < inner < outer and 1 <= outer <= 1024, found 7 and {radius}"
+                )
+            );
         }
+        let largest = with_word(outer_radius, SmoothLifeParams::MAX_RADIUS.to_bits());
+        assert!(SimulationParams::from_bytes(&largest).is_ok());
     }
 }
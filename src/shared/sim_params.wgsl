@@ -0,0 +1,94 @@
+// Prepended to every shader that binds the simulation parameters,
+// must match the layout of SimulationParams in sim_params.rs
+
+struct Rule {
+    // Bit tables indexed by neighborhood mask, packed into vec4s for uniform array alignment
+    birth: array<vec4<u32>, 2>,
+    survival: array<vec4<u32>, 2>,
+    // 2 for Life-like rules, more for Generations rules
+    states: u32,
+}
+
+struct PhysarumParams {
+    sensor_angle: f32,
+    sensor_distance: f32,
+    turn_speed: f32,
+    move_speed: f32,
+    deposit: f32,
+    decay: f32,
+    diffuse: f32,
+    agent_count: u32,
+}
+
+struct GrayScottParams {
+    feed: f32,
+    kill: f32,
+    diffusion_u: f32,
+    diffusion_v: f32,
+    dt: f32,
+}
+
+struct LeniaParams {
+    radius: u32,
+    mu: f32,
+    sigma: f32,
+    dt: f32,
+    peaks: vec4<f32>,
+    peak_count: u32,
+}
+
+struct SmoothLifeParams {
+    inner_radius: f32,
+    outer_radius: f32,
+    birth: vec2<f32>,
+    death: vec2<f32>,
+    alpha_n: f32,
+    alpha_m: f32,
+    dt: f32,
+    continuous: u32,
+    // Keeps the next member on a 16 byte boundary
+    _padding: vec2<u32>,
+}
+
+struct LargerThanLifeRule {
+    range: u32,
+    states: u32,
+    middle: u32,
+    neighborhood: u32,
+    survival: vec2<u32>,
+    birth: vec2<u32>,
+}
+
+// Discriminants of Neighborhood
+const NEIGHBORHOOD_MOORE = 0u;
+const NEIGHBORHOOD_VON_NEUMANN = 1u;
+const NEIGHBORHOOD_CIRCULAR = 2u;
+
+// Discriminants of Boundary
+const BOUNDARY_TORUS = 0u;
+const BOUNDARY_DEAD = 1u;
+const BOUNDARY_ALIVE = 2u;
+const BOUNDARY_MIRROR = 3u;
+const BOUNDARY_KLEIN_BOTTLE = 4u;
+const BOUNDARY_CROSS_SURFACE = 5u;
+
+// Discriminants of SimulationMode
+const MODE_LIFE = 0u;
+const MODE_PHYSARUM = 1u;
+const MODE_GRAY_SCOTT = 2u;
+const MODE_LENIA = 3u;
+const MODE_SMOOTH_LIFE = 4u;
+const MODE_LARGER_THAN_LIFE = 5u;
+
+struct SimulationParams {
+    width: u32,
+    height: u32,
+    mode: u32,
+    boundary: u32,
+    rule: Rule,
+    physarum: PhysarumParams,
+    gray_scott: GrayScottParams,
+    lenia: LeniaParams,
+    smooth_life: SmoothLifeParams,
+    larger_than_life: LargerThanLifeRule,
+}
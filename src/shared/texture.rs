@@ -1,4 +1,7 @@
+use anyhow::{bail, Result};
+
 pub struct Texture {
+    pub texture: wgpu::Texture,
     pub texture_view: wgpu::TextureView,
     pub texture_format: wgpu::TextureFormat,
 }
@@ -19,7 +22,13 @@ impl Texture {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            usage: wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            // Sampled by the renderer and the compute pass that reads the previous generation,
+            // written as storage by the pass that produces the next one,
+            // and copied in and out for initial state and readback
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::COPY_DST,
             format,
             view_formats: &[format],
         });
@@ -27,8 +36,126 @@ impl Texture {
         let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
 
         Self {
+            texture,
             texture_view,
             texture_format: format,
         }
     }
+
+    /// f32 values per texel
+    pub fn channels(&self) -> usize {
+        self.texture_format.block_size(None).unwrap() as usize / std::mem::size_of::<f32>()
+    }
+
+    /// Overwrites the whole texture with tightly packed rows of f32 texels
+    pub fn write(&self, queue: &wgpu::Queue, data: &[f32]) {
+        let size = self.texture.size();
+        let row_bytes = size.width * self.texture_format.block_size(None).unwrap();
+        queue.write_texture(
+            self.texture.as_image_copy(),
+            bytemuck::cast_slice(data),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(row_bytes),
+                rows_per_image: None,
+            },
+            size,
+        );
+    }
+
+    /// Overwrites a run of texels within one row, from the given texel rightwards,
+    /// with tightly packed f32 texels
+    pub fn write_row(&self, queue: &wgpu::Queue, origin: [u32; 2], data: &[f32]) {
+        let texel_bytes = self.texture_format.block_size(None).unwrap();
+        let width = std::mem::size_of_val(data) as u32 / texel_bytes;
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: origin[0],
+                    y: origin[1],
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            bytemuck::cast_slice(data),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width * texel_bytes),
+                rows_per_image: None,
+            },
+            wgpu::Extent3d {
+                width,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Copies the texture into a mappable buffer and blocks until its contents are on the CPU,
+    /// returned as tightly packed rows of f32 texels.
+    /// Fails when the padded rows need a larger buffer than the adapter allows
+    pub fn read(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<Vec<f32>> {
+        let size = self.texture.size();
+        let row_bytes = size.width * self.texture_format.block_size(None).unwrap();
+        // Buffer copies need rows aligned to 256 bytes, the padding is stripped after mapping
+        let padded_row_bytes = row_bytes.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer_size = u64::from(padded_row_bytes) * u64::from(size.height);
+        let max_size = device.limits().max_buffer_size;
+        if buffer_size > max_size {
+            bail!(
+                "Reading back the {}x{} texture needs a buffer of {buffer_size} bytes, \
+                more than the adapter's limit of {max_size}",
+                size.width,
+                size.height
+            );
+        }
+
+        let readback_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Texture Readback Buffer"),
+            size: buffer_size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut command_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Texture Readback Encoder"),
+        });
+        command_encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback_buf,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            size,
+        );
+        queue.submit(Some(command_encoder.finish()));
+
+        let buf_slice = readback_buf.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        buf_slice.map_async(wgpu::MapMode::Read, move |result| {
+            sender.send(result).unwrap();
+        });
+        device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .unwrap()
+            .expect("Failed to map texture readback buffer");
+
+        let mapped = buf_slice.get_mapped_range();
+        let data = mapped
+            .chunks(padded_row_bytes as usize)
+            .flat_map(|row| bytemuck::cast_slice::<u8, f32>(&row[..row_bytes as usize]))
+            .copied()
+            .collect();
+        drop(mapped);
+        readback_buf.unmap();
+
+        Ok(data)
+    }
 }
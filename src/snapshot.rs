@@ -0,0 +1,419 @@
+use crate::{compute::simulation::Simulation, shared::sim_params::SimulationParams};
+use anyhow::{anyhow, bail, Context, Result};
+use std::path::Path;
+
+/// First bytes of every snapshot file
+const MAGIC: &[u8; 8] = b"CELLSNAP";
+
+/// Raised whenever the layout of snapshots or of the parameters in them changes
+const VERSION: u32 = 1;
+
+/// Everything needed to continue a simulation bit for bit, on this machine or another.
+///
+/// Saved little endian, after the magic and version:
+/// the byte length and bytes of `SimulationParams`, the generation, the seed,
+/// the channels per texel, the f32 count and values of each ping-pong texture,
+/// then the count of state buffers and the byte length and bytes of each
+pub struct Snapshot {
+    pub params: SimulationParams,
+    pub generation: usize,
+    pub seed: u64,
+    /// f32 values per texel in `textures`
+    pub channels: usize,
+    /// Both ping-pong textures, tightly packed in the order indexed by [`Simulation::current`]
+    pub textures: [Vec<f32>; 2],
+    /// Mode-specific state outside the textures, like Physarum agents
+    pub state_buffers: Vec<Vec<u8>>,
+}
+
+/// Reads the fields of a snapshot in order, failing clearly on truncated files
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize, what: &str) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            bail!("Snapshot ends within the {what}");
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self, what: &str) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4, what)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self, what: &str) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8, what)?.try_into().unwrap()))
+    }
+
+    /// A length of at most the rest of the file, so corrupt lengths fail before allocating
+    fn count(&mut self, unit: usize, what: &str) -> Result<usize> {
+        let len = self.u64(what)?;
+        usize::try_from(len)
+            .ok()
+            .filter(|&len| len.saturating_mul(unit) <= self.bytes.len())
+            .ok_or_else(|| anyhow!("Snapshot ends within the {what}"))
+    }
+}
+
+impl Snapshot {
+    /// Reads the latest state of the simulation back from the GPU
+    pub fn capture(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        simulation: &Simulation,
+    ) -> Result<Self> {
+        let textures = simulation.textures();
+        Ok(Self {
+            params: *simulation.params(),
+            generation: simulation.generation,
+            seed: simulation.seed,
+            channels: textures[0].channels(),
+            textures: [
+                textures[0].read(device, queue)?,
+                textures[1].read(device, queue)?,
+            ],
+            state_buffers: simulation.read_state_buffers(device, queue),
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.to_bytes())
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend(MAGIC);
+        bytes.extend(VERSION.to_le_bytes());
+        let params = self.params.to_bytes();
+        bytes.extend((params.len() as u32).to_le_bytes());
+        bytes.extend(params);
+        bytes.extend((self.generation as u64).to_le_bytes());
+        bytes.extend(self.seed.to_le_bytes());
+        bytes.extend((self.channels as u32).to_le_bytes());
+        for cells in &self.textures {
+            bytes.extend((cells.len() as u64).to_le_bytes());
+            bytes.extend(cells.iter().flat_map(|cell| cell.to_le_bytes()));
+        }
+        bytes.extend((self.state_buffers.len() as u32).to_le_bytes());
+        for buffer in &self.state_buffers {
+            bytes.extend((buffer.len() as u64).to_le_bytes());
+            bytes.extend(buffer);
+        }
+        bytes
+    }
+
+    /// Reads a snapshot, checking its version and that its textures fill its grid
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes =
+            std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&bytes).with_context(|| format!("Invalid snapshot {}", path.display()))
+    }
+
+    fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader { bytes };
+        if reader.take(MAGIC.len(), "magic").ok() != Some(&MAGIC[..]) {
+            bail!("Not a snapshot file");
+        }
+        let version = reader.u32("version")?;
+        if version != VERSION {
+            bail!("Snapshot is version {version}, this build reads version {VERSION}");
+        }
+        let params_len = reader.u32("parameters")? as usize;
+        let params = SimulationParams::from_bytes(reader.take(params_len, "parameters")?)?;
+        let generation = reader.u64("generation")?;
+        let generation = usize::try_from(generation)
+            .map_err(|_| anyhow!("Generation {generation} is too large for this machine"))?;
+        let seed = reader.u64("seed")?;
+        let channels = reader.u32("channels")? as usize;
+        if !(1..=4).contains(&channels) {
+            bail!("Expected 1 to 4 channels per texel, found {channels}");
+        }
+
+        let expected = (params.width as usize)
+            .checked_mul(params.height as usize)
+            .and_then(|cells| cells.checked_mul(channels))
+            .ok_or_else(|| {
+                anyhow!(
+                    "A {}x{} grid with {channels} channels is incompatible with this machine",
+                    params.width,
+                    params.height
+                )
+            })?;
+        let mut read_texture = |index: usize| -> Result<Vec<f32>> {
+            let what = format!("texture {index}");
+            let len = reader.count(std::mem::size_of::<f32>(), &what)?;
+            if len != expected {
+                bail!(
+                    "Expected {expected} values in texture {index} of a {}x{} grid \
+                    with {channels} channels, found {len}",
+                    params.width,
+                    params.height
+                );
+            }
+            Ok(reader
+                .take(len * std::mem::size_of::<f32>(), &what)?
+                .chunks_exact(std::mem::size_of::<f32>())
+                .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+                .collect())
+        };
+        let textures = [read_texture(0)?, read_texture(1)?];
+
+        let buffer_count = reader.u32("state buffer count")?;
+        let state_buffers = (0..buffer_count)
+            .map(|index| {
+                let what = format!("state buffer {index}");
+                let len = reader.count(1, &what)?;
+                Ok(reader.take(len, &what)?.to_vec())
+            })
+            .collect::<Result<_>>()?;
+        if !reader.bytes.is_empty() {
+            bail!("Unexpected {} bytes after the snapshot", reader.bytes.len());
+        }
+
+        Ok(Self {
+            params,
+            generation,
+            seed,
+            channels,
+            textures,
+            state_buffers,
+        })
+    }
+
+    /// Continues `simulation`, built from the snapshot's parameters and seed, from the snapshot.
+    /// Textures are repacked when the adapter stores a different number of channels per texel,
+    /// as Gray-Scott's two channels are padded to four on downlevel adapters
+    pub fn restore(&self, queue: &wgpu::Queue, simulation: &mut Simulation) -> Result<()> {
+        let channels = simulation.textures()[0].channels();
+        let textures = self.textures.clone().map(|cells| {
+            if channels == self.channels {
+                return cells;
+            }
+            cells
+                .chunks_exact(self.channels)
+                .flat_map(|texel| {
+                    (0..channels).map(|channel| texel.get(channel).copied().unwrap_or(0.0))
+                })
+                .collect()
+        });
+        simulation
+            .restore(queue, self.generation, &textures, &self.state_buffers)
+            .context("Snapshot does not match the simulation built from it")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        compute::simulation::{tests::compute_gpu, ComputeBackend},
+        shared::sim_params::{
+            GrayScottParams, LeniaParams, PhysarumParams, SimulationMode, SimulationParamsBuf,
+            SmoothLifeParams,
+        },
+    };
+    use std::mem::offset_of;
+
+    /// Parameters start after the magic, the version and their byte length
+    const PARAMS_OFFSET: usize = MAGIC.len() + 8;
+
+    fn snapshot(mode: SimulationMode) -> Snapshot {
+        let params = SimulationParams::new(&winit::dpi::PhysicalSize::new(4, 3), mode);
+        Snapshot {
+            params,
+            generation: 7,
+            seed: 42,
+            channels: 1,
+            textures: [vec![0.25; 12], vec![0.75; 12]],
+            state_buffers: vec![vec![1, 2, 3]],
+        }
+    }
+
+    /// The saved snapshot with the parameter word at byte `offset` replaced
+    fn with_param_word(mode: SimulationMode, offset: usize, word: u32) -> Vec<u8> {
+        let mut bytes = snapshot(mode).to_bytes();
+        let offset = PARAMS_OFFSET + offset;
+        bytes[offset..offset + 4].copy_from_slice(&word.to_le_bytes());
+        bytes
+    }
+
+    fn error(bytes: &[u8]) -> String {
+        format!("{:#}", Snapshot::parse(bytes).err().unwrap())
+    }
+
+    #[test]
+    fn round_trips_bytes() {
+        let bytes = snapshot(SimulationMode::Physarum).to_bytes();
+        let read = Snapshot::parse(&bytes).unwrap();
+        assert_eq!(read.to_bytes(), bytes);
+        assert_eq!(read.params.mode(), SimulationMode::Physarum);
+        assert_eq!((read.generation, read.seed, read.channels), (7, 42, 1));
+        assert_eq!(read.textures, [vec![0.25; 12], vec![0.75; 12]]);
+        assert_eq!(read.state_buffers, [vec![1, 2, 3]]);
+    }
+
+    #[test]
+    fn refuses_truncated_files() {
+        let bytes = snapshot(SimulationMode::Life).to_bytes();
+        for len in 0..bytes.len() {
+            assert!(Snapshot::parse(&bytes[..len]).is_err(), "length {len}");
+        }
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert_eq!(error(&longer), "Unexpected 1 bytes after the snapshot");
+    }
+
+    #[test]
+    fn refuses_other_versions() {
+        let mut bytes = snapshot(SimulationMode::Life).to_bytes();
+        bytes[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert_eq!(
+            error(&bytes),
+            format!(
+                "Snapshot is version {}, this build reads version {VERSION}",
+                VERSION + 1
+            )
+        );
+        bytes[0] = b'X';
+        assert_eq!(error(&bytes), "Not a snapshot file");
+    }
+
+    #[test]
+    fn refuses_parameters_out_of_range() {
+        let lenia = offset_of!(SimulationParams, lenia);
+        let peak_count = lenia + offset_of!(LeniaParams, peak_count);
+        for count in [0, 5, 7] {
+            assert_eq!(
+                error(&with_param_word(SimulationMode::Lenia, peak_count, count)),
+                format!(
+                    "Invalid Lenia parameters: Lenia kernels have one to four peaks, found {count}"
+                )
+            );
+        }
+        let radius = lenia + offset_of!(LeniaParams, radius);
+        for radius_cells in [0, 1 << 16, u32::MAX] {
+            assert_eq!(
+                error(&with_param_word(
+                    SimulationMode::Lenia,
+                    radius,
+                    radius_cells
+                )),
+                format!(
+                    "Invalid Lenia parameters: \
+                    Lenia kernel radius must be 1 to 1024 cells, found {radius_cells}"
+                )
+            );
+        }
+        let outer_radius =
+            offset_of!(SimulationParams, smooth_life) + offset_of!(SmoothLifeParams, outer_radius);
+        for radius in [1e9, f32::MAX] {
+            assert_eq!(
+                error(&with_param_word(
+                    SimulationMode::SmoothLife,
+                    outer_radius,
+                    radius.to_bits()
+                )),
+                format!(
+                    "Invalid SmoothLife parameters: SmoothLife radii must satisfy \
+                    0 < inner < outer and 1 <= outer <= 1024, found 7 and {radius}"
+                )
+            );
+        }
+
+        let agent_count =
+            offset_of!(SimulationParams, physarum) + offset_of!(PhysarumParams, agent_count);
+        assert_eq!(
+            error(&with_param_word(SimulationMode::Physarum, agent_count, 0)),
+            "Invalid Physarum parameters: Physarum needs at least one agent, found 0"
+        );
+
+        let feed = offset_of!(SimulationParams, gray_scott) + offset_of!(GrayScottParams, feed);
+        assert_eq!(
+            error(&with_param_word(
+                SimulationMode::GrayScott,
+                feed,
+                f32::NAN.to_bits()
+            )),
+            "Invalid Gray-Scott parameters: Expected a finite feed rate, found NaN"
+        );
+    }
+
+    #[test]
+    fn refuses_grids_too_large_to_address() {
+        let width = offset_of!(SimulationParams, width);
+        let mut bytes = with_param_word(SimulationMode::Life, width, u32::MAX);
+        let height = PARAMS_OFFSET + offset_of!(SimulationParams, height);
+        bytes[height..height + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        // The channel count follows the parameters, the generation and the seed
+        let channels = PARAMS_OFFSET + std::mem::size_of::<SimulationParams>() + 16;
+        bytes[channels..channels + 4].copy_from_slice(&4_u32.to_le_bytes());
+        assert_eq!(
+            error(&bytes),
+            "A 4294967295x4294967295 grid with 4 channels is incompatible with this machine"
+        );
+    }
+
+    /// Continuing from a saved snapshot steps exactly as the simulation it was captured from
+    #[tokio::test]
+    async fn resumes_where_captured() {
+        let Some(gpu) = compute_gpu().await else {
+            return;
+        };
+        let mut params = SimulationParams::new(
+            &winit::dpi::PhysicalSize::new(32, 24),
+            SimulationMode::Physarum,
+        );
+        params.physarum.agent_count = 256;
+        let sim_params = SimulationParamsBuf::new(&gpu.device, params);
+        let build = |seed| {
+            Simulation::new(
+                &gpu.device,
+                &gpu.queue,
+                &gpu.adapter,
+                &sim_params,
+                seed,
+                ComputeBackend::Gpu,
+            )
+            .unwrap()
+        };
+        let step = |simulation: &mut Simulation, steps| {
+            let command_buffers: Vec<_> = (0..steps)
+                .map(|_| simulation.step(&gpu.device, &gpu.queue).finish())
+                .collect();
+            gpu.queue.submit(command_buffers);
+        };
+
+        let mut simulation = build(5);
+        step(&mut simulation, 3);
+        let path = std::env::temp_dir().join(format!("cells-snapshot-{}.snap", std::process::id()));
+        Snapshot::capture(&gpu.device, &gpu.queue, &simulation)
+            .unwrap()
+            .save(&path)
+            .unwrap();
+        let snapshot = Snapshot::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let snapshot = snapshot.unwrap();
+        assert_eq!(snapshot.generation, 3);
+        assert_eq!(snapshot.seed, 5);
+
+        // Built from another seed, so only the snapshot can make it match
+        let mut resumed = build(6);
+        snapshot.restore(&gpu.queue, &mut resumed).unwrap();
+        assert_eq!(
+            resumed.read_cells(&gpu.device, &gpu.queue).unwrap(),
+            simulation.read_cells(&gpu.device, &gpu.queue).unwrap()
+        );
+        step(&mut simulation, 2);
+        step(&mut resumed, 2);
+        assert_eq!(resumed.generation, 5);
+        assert_eq!(
+            resumed.read_cells(&gpu.device, &gpu.queue).unwrap(),
+            simulation.read_cells(&gpu.device, &gpu.queue).unwrap()
+        );
+    }
+}